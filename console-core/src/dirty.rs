@@ -0,0 +1,280 @@
+//! Dirty-rectangle tracking for the LCD.
+//!
+//! The panel is split into 8x8 pixel tiles. Every frame the scene is first
+//! rendered into a per-tile hash of the pixels it draws; only tiles whose hash
+//! differs from what is already on the panel are sent. Each horizontal run of
+//! dirty tiles is composed in a small RAM band (background plus the scene
+//! clipped to the band) and written in one go, so nothing is ever painted
+//! black first and games do not need any erase logic.
+
+use core::convert::Infallible;
+
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::framebuffer::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::render::Render;
+
+pub const TILE_SIZE: usize = 8;
+const TILES_X: usize = SCREEN_WIDTH / TILE_SIZE;
+const TILES_Y: usize = SCREEN_HEIGHT / TILE_SIZE;
+
+const HASH_OFFSET: u32 = 0x811c_9dc5;
+const HASH_PRIME: u32 = 0x0100_0193;
+
+type TileHashes = [[u32; TILES_X]; TILES_Y];
+
+pub struct DirtyDisplay<D> {
+    display: D,
+    background: Rgb565,
+    shown: TileHashes,
+    valid: bool,
+    band: [Rgb565; SCREEN_WIDTH * TILE_SIZE],
+}
+
+impl<D> DirtyDisplay<D>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    pub fn new(display: D, background: Rgb565) -> Self {
+        DirtyDisplay {
+            display,
+            background,
+            shown: [[HASH_OFFSET; TILES_X]; TILES_Y],
+            valid: false,
+            band: [background; SCREEN_WIDTH * TILE_SIZE],
+        }
+    }
+
+    /// Forgets what is on the panel, so the next frame is sent in full
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Direct access to the display. Anything drawn here is not tracked,
+    /// call `invalidate` afterwards.
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.display
+    }
+
+    pub fn release(self) -> D {
+        self.display
+    }
+
+    /// Draws `scene` on the panel, sending only the tiles that changed since
+    /// the previous frame. Returns the number of tiles sent.
+    pub fn draw_frame<R>(&mut self, scene: &R) -> Result<usize, D::Error>
+    where
+        R: Render + ?Sized,
+    {
+        let mut hasher = TileHasher { hashes: [[HASH_OFFSET; TILES_X]; TILES_Y] };
+        match scene.render(&mut hasher) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+
+        let mut flushed = 0;
+        for ty in 0..TILES_Y {
+            let mut tx = 0;
+            while tx < TILES_X {
+                if !self.is_dirty(&hasher.hashes, tx, ty) {
+                    tx += 1;
+                    continue;
+                }
+
+                let start = tx;
+                while tx < TILES_X && self.is_dirty(&hasher.hashes, tx, ty) {
+                    tx += 1;
+                }
+
+                let area = Rectangle::new(
+                    Point::new((start * TILE_SIZE) as i32, (ty * TILE_SIZE) as i32),
+                    Size::new(((tx - start) * TILE_SIZE) as u32, TILE_SIZE as u32),
+                );
+                self.flush_band(scene, area)?;
+                flushed += tx - start;
+            }
+        }
+
+        self.shown = hasher.hashes;
+        self.valid = true;
+        Ok(flushed)
+    }
+
+    fn is_dirty(&self, hashes: &TileHashes, tx: usize, ty: usize) -> bool {
+        !self.valid || self.shown[ty][tx] != hashes[ty][tx]
+    }
+
+    fn flush_band<R>(&mut self, scene: &R, area: Rectangle) -> Result<(), D::Error>
+    where
+        R: Render + ?Sized,
+    {
+        let len = (area.size.width * area.size.height) as usize;
+        let pixels = &mut self.band[..len];
+        pixels.fill(self.background);
+
+        let mut band = Band { area, pixels };
+        match scene.render(&mut band) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+
+        self.display.fill_contiguous(&area, self.band[..len].iter().copied())
+    }
+}
+
+/// Accumulates a hash of every pixel drawn into the tile it lands in
+struct TileHasher {
+    hashes: TileHashes,
+}
+
+impl TileHasher {
+    fn add(&mut self, point: Point, color: Rgb565) {
+        if point.x < 0 || point.y < 0 || point.x as usize >= SCREEN_WIDTH || point.y as usize >= SCREEN_HEIGHT {
+            return;
+        }
+        let key = (point.y as u32) << 24 | (point.x as u32) << 16 | RawU16::from(color).into_inner() as u32;
+        let hash = &mut self.hashes[point.y as usize / TILE_SIZE][point.x as usize / TILE_SIZE];
+        *hash = (*hash ^ key).wrapping_mul(HASH_PRIME);
+    }
+}
+
+impl OriginDimensions for TileHasher {
+    fn size(&self) -> Size {
+        Size::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for TileHasher {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            self.add(point, color);
+        }
+        Ok(())
+    }
+}
+
+/// A horizontal strip of the screen composed in RAM before it is sent
+struct Band<'a> {
+    area: Rectangle,
+    pixels: &'a mut [Rgb565],
+}
+
+impl Band<'_> {
+    fn index(&self, point: Point) -> usize {
+        let offset = point - self.area.top_left;
+        offset.y as usize * self.area.size.width as usize + offset.x as usize
+    }
+}
+
+impl OriginDimensions for Band<'_> {
+    fn size(&self) -> Size {
+        Size::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Band<'_> {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if self.area.contains(point) {
+                let index = self.index(point);
+                self.pixels[index] = color;
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let clipped = area.intersection(&self.area);
+        if clipped.is_zero_sized() {
+            return Ok(());
+        }
+
+        let width = clipped.size.width as usize;
+        for y in 0..clipped.size.height as i32 {
+            let start = self.index(clipped.top_left + Point::new(0, y));
+            self.pixels[start..start + width].fill(color);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+    use embedded_graphics::primitives::PrimitiveStyle;
+
+    struct Square {
+        top_left: Point,
+    }
+
+    impl Render for Square {
+        fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+        where
+            D: DrawTarget<Color = Rgb565>,
+        {
+            Rectangle::new(self.top_left, Size::new(2, 2))
+                .into_styled(PrimitiveStyle::with_fill(Rgb565::RED))
+                .draw(target)?;
+            Pixel(Point::new(100, 100), Rgb565::WHITE).draw(target)
+        }
+    }
+
+    fn assert_matches_direct_render(display: &Framebuffer, scene: &Square) {
+        let mut expected: Framebuffer = Framebuffer::new();
+        scene.render(&mut expected).unwrap();
+        assert!(display.as_bytes() == expected.as_bytes());
+    }
+
+    #[test]
+    fn first_frame_is_sent_in_full() {
+        let mut display = DirtyDisplay::new(Framebuffer::<SCREEN_WIDTH, SCREEN_HEIGHT>::new(), Rgb565::BLACK);
+        let scene = Square { top_left: Point::new(10, 10) };
+
+        assert_eq!(display.draw_frame(&scene).unwrap(), TILES_X * TILES_Y);
+        assert_matches_direct_render(&display.display, &scene);
+    }
+
+    #[test]
+    fn unchanged_frame_sends_nothing() {
+        let mut display = DirtyDisplay::new(Framebuffer::<SCREEN_WIDTH, SCREEN_HEIGHT>::new(), Rgb565::BLACK);
+        let scene = Square { top_left: Point::new(10, 10) };
+
+        display.draw_frame(&scene).unwrap();
+        assert_eq!(display.draw_frame(&scene).unwrap(), 0);
+
+        display.invalidate();
+        assert_eq!(display.draw_frame(&scene).unwrap(), TILES_X * TILES_Y);
+    }
+
+    #[test]
+    fn moving_object_repaints_old_and_new_tiles_only() {
+        let mut display = DirtyDisplay::new(Framebuffer::<SCREEN_WIDTH, SCREEN_HEIGHT>::new(), Rgb565::BLACK);
+        display.draw_frame(&Square { top_left: Point::new(10, 10) }).unwrap();
+
+        // Moves from tile (1, 1) across the corner into tiles (1..=2, 1..=2)
+        let scene = Square { top_left: Point::new(15, 15) };
+        assert_eq!(display.draw_frame(&scene).unwrap(), 4);
+        assert_matches_direct_render(&display.display, &scene);
+
+        // Moving inside the same tiles still counts as a change
+        let scene = Square { top_left: Point::new(14, 15) };
+        assert_eq!(display.draw_frame(&scene).unwrap(), 4);
+        assert_matches_direct_render(&display.display, &scene);
+    }
+}
@@ -0,0 +1,124 @@
+use core::convert::Infallible;
+
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 128;
+
+/// Off-screen copy of the whole panel. Pixels are stored in the byte order
+/// the ST7735 expects on the wire (big-endian RGB565), so the buffer can be
+/// streamed to the LCD as is.
+pub struct Framebuffer<const W: usize = SCREEN_WIDTH, const H: usize = SCREEN_HEIGHT> {
+    pixels: [[u16; W]; H],
+}
+
+impl<const W: usize, const H: usize> Framebuffer<W, H> {
+    pub const fn new() -> Self {
+        Framebuffer { pixels: [[0; W]; H] }
+    }
+
+    /// Colour at `(x, y)`, `None` outside the buffer
+    pub fn pixel(&self, x: i32, y: i32) -> Option<Rgb565> {
+        if x < 0 || y < 0 || x as usize >= W || y as usize >= H {
+            return None;
+        }
+        let raw = u16::from_be(self.pixels[y as usize][x as usize]);
+        Some(Rgb565::from(RawU16::new(raw)))
+    }
+
+    /// The whole buffer, row by row, ready to be sent to the panel
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `[[u16; W]; H]` is a contiguous block of `W * H * 2` bytes
+        // without padding and every byte pattern is a valid `u8`
+        unsafe { core::slice::from_raw_parts(self.pixels.as_ptr() as *const u8, W * H * 2) }
+    }
+
+    fn encode(color: Rgb565) -> u16 {
+        RawU16::from(color).into_inner().to_be()
+    }
+}
+
+impl<const W: usize, const H: usize> Default for Framebuffer<W, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize, const H: usize> OriginDimensions for Framebuffer<W, H> {
+    fn size(&self) -> Size {
+        Size::new(W as u32, H as u32)
+    }
+}
+
+impl<const W: usize, const H: usize> DrawTarget for Framebuffer<W, H> {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 && (point.x as usize) < W && (point.y as usize) < H {
+                self.pixels[point.y as usize][point.x as usize] = Self::encode(color);
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+
+        let x = area.top_left.x as usize;
+        let width = area.size.width as usize;
+        let raw = Self::encode(color);
+        for row in self.pixels.iter_mut().skip(area.top_left.y as usize).take(area.size.height as usize) {
+            row[x..x + width].fill(raw);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.pixels = [[Self::encode(color); W]; H];
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::primitives::PrimitiveStyle;
+
+    #[test]
+    fn bytes_are_big_endian_rows() {
+        let mut framebuffer: Framebuffer<4, 2> = Framebuffer::new();
+        let color = Rgb565::new(0b10101, 0b110011, 0b01010);
+        Pixel(Point::new(1, 1), color).draw(&mut framebuffer).unwrap();
+
+        let raw = RawU16::from(color).into_inner();
+        let offset = (4 + 1) * 2;
+        assert_eq!(framebuffer.as_bytes()[offset], (raw >> 8) as u8);
+        assert_eq!(framebuffer.as_bytes()[offset + 1], raw as u8);
+        assert_eq!(framebuffer.pixel(1, 1), Some(color));
+    }
+
+    #[test]
+    fn fill_solid_is_clipped() {
+        let mut framebuffer: Framebuffer<8, 8> = Framebuffer::new();
+        Rectangle::new(Point::new(6, -2), Size::new(4, 4))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::WHITE))
+            .draw(&mut framebuffer)
+            .unwrap();
+
+        assert_eq!(framebuffer.pixel(6, 0), Some(Rgb565::WHITE));
+        assert_eq!(framebuffer.pixel(7, 1), Some(Rgb565::WHITE));
+        assert_eq!(framebuffer.pixel(7, 2), Some(Rgb565::BLACK));
+        assert_eq!(framebuffer.pixel(5, 0), Some(Rgb565::BLACK));
+    }
+}
@@ -0,0 +1,275 @@
+//! Registry of the games on the console.
+//!
+//! The menu lists `GAMES` in order and a game's index in it is also its high
+//! score table, so new entries go at the end. Adding a game means adding its
+//! engine as a `Game` variant with the matching arms below and registering it
+//! in `GAMES`.
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+
+use crate::input::InputSnapshot;
+use crate::pong::{self, PlayerTurn, Pong};
+use crate::pong_four::{FourPong, Side};
+use crate::render::Render;
+use crate::scores::{HighScores, MAX_GAMES};
+use crate::settings::Settings;
+use crate::snake::{self, Snake};
+
+pub struct GameEntry {
+    pub name: &'static str,
+    /// Builds a fresh game for a `width` x `height` playfield from a seed,
+    /// the settings and the save data, for progress such as the campaign level
+    pub start: fn(i16, i16, u64, &Settings, &HighScores) -> Game,
+    /// Attract mode: nobody plays, it loops until a button is pressed and
+    /// keeps no scores
+    pub demo: bool,
+}
+
+pub static GAMES: [GameEntry; 6] = [
+    GameEntry {
+        name: "Pong",
+        start: |width, height, seed, settings, _| {
+            let pong = Pong::with_config(width, height, seed, settings.pong)
+                .with_stick_mode(PlayerTurn::Player1, settings.stick_modes[0])
+                .with_stick_mode(PlayerTurn::Player2, settings.stick_modes[1]);
+            Game::Pong(pong)
+        },
+        demo: false,
+    },
+    GameEntry {
+        name: "Snake",
+        start: |width, height, seed, settings, _| {
+            let snake = Snake::for_screen(width, height, snake::CELL_SIZE, settings.snake_arena, seed)
+                .with_speed(settings.snake_difficulty, settings.snake_speed_up);
+            Game::Snake(snake)
+        },
+        demo: false,
+    },
+    GameEntry {
+        name: "Pong 1P vs CPU",
+        start: |width, height, seed, settings, _| {
+            let cpu = settings.cpu_difficulty.params();
+            let pong = Pong::with_config(width, height, seed, settings.pong)
+                .with_stick_mode(PlayerTurn::Player1, settings.stick_modes[0])
+                .with_cpu(PlayerTurn::Player2, cpu, seed ^ 2);
+            Game::Pong(pong)
+        },
+        demo: false,
+    },
+    GameEntry {
+        name: "Pong CPU vs CPU",
+        start: |width, height, seed, settings, _| {
+            let cpu = settings.cpu_difficulty.params();
+            let pong = Pong::with_config(width, height, seed, settings.pong)
+                .with_cpu(PlayerTurn::Player1, cpu, seed ^ 1)
+                .with_cpu(PlayerTurn::Player2, cpu, seed ^ 2);
+            Game::Pong(pong)
+        },
+        demo: true,
+    },
+    GameEntry {
+        name: "Pong 4 players",
+        start: |width, height, seed, _, _| Game::FourPong(FourPong::new(width, height, seed)),
+        demo: false,
+    },
+    GameEntry {
+        name: "Snake campaign",
+        start: |width, height, seed, settings, saved| {
+            let snake = Snake::campaign(width, height, snake::CELL_SIZE, saved.campaign_level(), seed)
+                .with_speed(settings.snake_difficulty, settings.snake_speed_up);
+            Game::Snake(snake)
+        },
+        demo: false,
+    },
+];
+
+// Every game needs its own table in the save record
+const _: () = assert!(GAMES.len() <= MAX_GAMES);
+
+/// How a game ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameResult {
+    /// Points of the last set and sets won, `against_cpu` when the computer
+    /// played the right paddle
+    Pong { player1_score: u8, player2_score: u8, player1_sets: u8, player2_sets: u8, against_cpu: bool },
+    /// `won` when the snake filled the whole board or finished the campaign,
+    /// `level` is the campaign level it got to
+    Snake { won: bool, length: usize, score: u16, level: Option<u8> },
+    /// The last side standing and the lives it has left
+    FourPong { winner: Side, lives: u8 },
+}
+
+impl GameResult {
+    /// Score for the high score table. Pong scores the winner's margin in the
+    /// last set, only a win counts against the computer. Snake scores the food
+    /// eaten, so crashing straight away is no high score.
+    pub fn score(&self) -> u16 {
+        match *self {
+            GameResult::Pong { player1_score, player2_score, against_cpu: true, .. } => {
+                player1_score.saturating_sub(player2_score) as u16
+            }
+            GameResult::Pong { player1_score, player2_score, .. } => player1_score.abs_diff(player2_score) as u16,
+            GameResult::Snake { score, .. } => score.saturating_sub(snake::SNAKE_INITIAL_LENGTH as u16),
+            GameResult::FourPong { lives, .. } => lives as u16,
+        }
+    }
+}
+
+// There is no heap on the console and only one game is alive at a time
+#[allow(clippy::large_enum_variant)]
+pub enum Game {
+    Pong(Pong),
+    Snake(Snake),
+    FourPong(FourPong),
+}
+
+impl Game {
+    pub fn update(&mut self, input: &InputSnapshot) {
+        match self {
+            Game::Pong(pong) => pong.update(input),
+            Game::Snake(snake) => snake.update(input),
+            Game::FourPong(pong) => pong.update(input),
+        }
+    }
+
+    /// The sticks read between two updates. Only Snake looks at them, it
+    /// queues the turns made within one move.
+    pub fn handle_input(&mut self, input: &InputSnapshot) {
+        if let Game::Snake(snake) = self {
+            snake.handle_input(input);
+        }
+    }
+
+    /// How often `update` should run right now
+    pub fn tick_period_us(&self) -> u32 {
+        match self {
+            Game::Pong(_) | Game::FourPong(_) => pong::TICK_PERIOD_US,
+            Game::Snake(snake) => snake.tick_period_us(),
+        }
+    }
+
+    /// Campaign level being played, to be kept in the save record
+    pub fn campaign_level(&self) -> Option<u8> {
+        match self {
+            Game::Snake(snake) => snake.campaign.map(|campaign| campaign.index),
+            _ => None,
+        }
+    }
+
+    /// How the game ended, `None` while it is still running
+    pub fn result(&self) -> Option<GameResult> {
+        match self {
+            Game::Pong(pong) if !pong.is_running => Some(GameResult::Pong {
+                player1_score: pong.player1_score,
+                player2_score: pong.player2_score,
+                player1_sets: pong.player1_sets,
+                player2_sets: pong.player2_sets,
+                against_cpu: pong.is_cpu(PlayerTurn::Player2),
+            }),
+            Game::Snake(snake) if !snake.alive || snake.won => Some(GameResult::Snake {
+                won: snake.won,
+                length: snake.body.len(),
+                score: snake.score,
+                level: snake.campaign.map(|campaign| campaign.index),
+            }),
+            Game::FourPong(pong) if !pong.is_running => {
+                pong.winner().map(|winner| GameResult::FourPong { winner, lives: pong.lives[winner as usize] })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Render for Game {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        match self {
+            Game::Pong(pong) => pong.render(target),
+            Game::Snake(snake) => snake.render(target),
+            Game::FourPong(pong) => pong.render(target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snake::SnakeDifficulty;
+
+    #[test]
+    fn registry_builds_each_game() {
+        for entry in GAMES.iter() {
+            let game = (entry.start)(160, 128, 1, &Settings::default(), &HighScores::new());
+            assert!(game.result().is_none(), "{} finished right away", entry.name);
+        }
+        assert!(matches!((GAMES[1].start)(160, 128, 1, &Settings::default(), &HighScores::new()), Game::Snake(_)));
+    }
+
+    #[test]
+    fn result_reports_the_end_of_the_game() {
+        let mut game = (GAMES[0].start)(160, 128, 1, &Settings::default(), &HighScores::new());
+        if let Game::Pong(ref mut pong) = game {
+            pong.player1_score = 4;
+            pong.player2_score = 11;
+            pong.check_for_win();
+        }
+        let result = game.result().unwrap();
+        let expected = GameResult::Pong {
+            player1_score: 4,
+            player2_score: 11,
+            player1_sets: 0,
+            player2_sets: 1,
+            against_cpu: false,
+        };
+        assert_eq!(result, expected);
+        assert_eq!(result.score(), 7);
+
+        // Losing to the computer is no high score
+        let lost = GameResult::Pong {
+            player1_score: 4,
+            player2_score: 11,
+            player1_sets: 0,
+            player2_sets: 1,
+            against_cpu: true,
+        };
+        assert_eq!(lost.score(), 0);
+
+        // A full board ends the game as a win while the snake is still alive
+        let mut game = (GAMES[1].start)(160, 128, 1, &Settings::default(), &HighScores::new());
+        if let Game::Snake(ref mut snake) = game {
+            snake.won = true;
+        }
+        assert!(matches!(game.result(), Some(GameResult::Snake { won: true, length: 3, .. })));
+
+        // Four player Pong scores the lives the last side kept
+        let mut game = (GAMES[4].start)(160, 128, 1, &Settings::default(), &HighScores::new());
+        if let Game::FourPong(ref mut pong) = game {
+            pong.lives = [0, 2, 0, 0];
+            pong.is_running = false;
+        }
+        let result = game.result().unwrap();
+        assert_eq!(result, GameResult::FourPong { winner: Side::Right, lives: 2 });
+        assert_eq!(result.score(), 2);
+
+        // Snake only counts the food eaten
+        let crashed = GameResult::Snake { won: false, length: 3, score: 3, level: None };
+        assert_eq!(crashed.score(), 0);
+    }
+
+    #[test]
+    fn campaign_resumes_at_the_saved_level() {
+        let mut saved = HighScores::new();
+        saved.reach_campaign_level(2);
+        let game = (GAMES[5].start)(160, 128, 1, &Settings::default(), &saved);
+        assert_eq!(game.campaign_level(), Some(2));
+        assert_eq!(game.tick_period_us(), 50_000);
+        assert_eq!((GAMES[1].start)(160, 128, 1, &Settings::default(), &saved).campaign_level(), None);
+
+        // The scheduler follows the speed picked in the settings
+        let settings = Settings { snake_difficulty: SnakeDifficulty::Insane, ..Settings::default() };
+        assert_eq!((GAMES[1].start)(160, 128, 1, &settings, &saved).tick_period_us(), 20_000);
+    }
+}
@@ -0,0 +1,157 @@
+pub const JOY_MAX_VAL: u16 = 4095;
+pub const JOY_CENTER_VAL: u16 = 2048;
+pub const JOY_UPPER_BOUND: u16 = 3071; // 3/4 of JOY_MAX_VALUE
+pub const JOY_LOWER_BOUND: u16 = 1024; // 1/4 of JOY_MAX_VALUE
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoyToPin {
+    JoyX1 = 0,
+    JoyY1 = 1,
+    JoyX2 = 2,
+    JoyY2 = 3
+}
+
+/// State of both joysticks sampled once per frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputSnapshot {
+    /// Raw 12-bit readings indexed by `JoyToPin`
+    pub axes: [u16; 4],
+    pub button1: bool,
+    pub button2: bool,
+}
+
+impl InputSnapshot {
+    pub const CENTERED: InputSnapshot = InputSnapshot {
+        axes: [JOY_CENTER_VAL; 4],
+        button1: false,
+        button2: false,
+    };
+
+    pub fn axis(&self, joy: JoyToPin) -> u16 {
+        self.axes[joy as usize]
+    }
+
+    pub fn with_axis(mut self, joy: JoyToPin, value: u16) -> Self {
+        self.axes[joy as usize] = value;
+        self
+    }
+
+    pub fn with_buttons(mut self, button1: bool, button2: bool) -> Self {
+        self.button1 = button1;
+        self.button2 = button2;
+        self
+    }
+}
+
+/// Anything that can produce a joystick snapshot: the mux on the board, the
+/// simulator keyboard or a script in tests
+pub trait InputSource {
+    fn read(&mut self) -> InputSnapshot;
+}
+
+/// Replays a fixed list of snapshots, one per `read`, then keeps returning
+/// centered sticks with no buttons pressed
+pub struct ScriptedInput<'a> {
+    frames: &'a [InputSnapshot],
+    position: usize,
+}
+
+impl<'a> ScriptedInput<'a> {
+    pub fn new(frames: &'a [InputSnapshot]) -> Self {
+        ScriptedInput { frames, position: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+}
+
+impl InputSource for ScriptedInput<'_> {
+    fn read(&mut self) -> InputSnapshot {
+        let snapshot = self.frames.get(self.position).copied().unwrap_or(InputSnapshot::CENTERED);
+        self.position += 1;
+        snapshot
+    }
+}
+
+/// Turns a stick held to one side into a single step, for menus and lists
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AxisEdge {
+    held: i8,
+}
+
+impl AxisEdge {
+    /// -1 or 1 on the tick the stick leaves the centre (or flips side), 0 otherwise
+    pub fn step(&mut self, value: u16) -> i8 {
+        let side = if value > JOY_UPPER_BOUND {
+            1
+        } else if value < JOY_LOWER_BOUND {
+            -1
+        } else {
+            0
+        };
+
+        let step = if side != self.held { side } else { 0 };
+        self.held = side;
+        step
+    }
+}
+
+/// Reports a button press once, on the tick it goes down
+#[derive(Clone, Copy, Debug)]
+pub struct ButtonEdge {
+    was_down: bool,
+}
+
+impl ButtonEdge {
+    /// A button that has to be released first, so the press that opened a
+    /// screen does not also confirm it
+    pub const fn held() -> Self {
+        ButtonEdge { was_down: true }
+    }
+
+    pub const fn released() -> Self {
+        ButtonEdge { was_down: false }
+    }
+
+    pub fn pressed(&mut self, down: bool) -> bool {
+        let pressed = down && !self.was_down;
+        self.was_down = down;
+        pressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripted_input_replays_then_centers() {
+        let frames = [
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY2, 0),
+            InputSnapshot::CENTERED.with_buttons(true, false),
+        ];
+        let mut input = ScriptedInput::new(&frames);
+
+        assert_eq!(input.read().axis(JoyToPin::JoyY2), 0);
+        assert!(input.read().button1);
+        assert!(input.is_finished());
+        assert_eq!(input.read(), InputSnapshot::CENTERED);
+    }
+
+    #[test]
+    fn edges_fire_once_per_press() {
+        let mut axis = AxisEdge::default();
+        assert_eq!(axis.step(JOY_MAX_VAL), 1);
+        assert_eq!(axis.step(JOY_MAX_VAL), 0);
+        assert_eq!(axis.step(0), -1);
+        assert_eq!(axis.step(JOY_CENTER_VAL), 0);
+        assert_eq!(axis.step(0), -1);
+
+        let mut button = ButtonEdge::held();
+        assert!(!button.pressed(true));
+        assert!(!button.pressed(false));
+        assert!(button.pressed(true));
+        assert!(!button.pressed(true));
+    }
+}
@@ -0,0 +1,30 @@
+//! Hardware-independent part of the console: the game engines, the menu state
+//! machine, the joystick thresholds and the save data format.
+//!
+//! Nothing in here touches the RP2040, so the logic can be unit-tested on the
+//! host with `cargo test -p console-core --target x86_64-unknown-linux-gnu`
+//! and shared between the firmware and the desktop simulator.
+
+#![no_std]
+// `is_none_or` and `is_multiple_of` need Rust 1.82 and 1.87, older compilers
+// still build the firmware
+#![allow(clippy::unnecessary_map_or, clippy::manual_is_multiple_of)]
+
+pub mod dirty;
+pub mod framebuffer;
+pub mod games;
+pub mod input;
+pub mod pong;
+pub mod pong_ai;
+pub mod pong_arcade;
+pub mod pong_four;
+pub mod render;
+pub mod scores;
+pub mod screens;
+pub mod settings;
+pub mod snake;
+pub mod snake_body;
+pub mod snake_level;
+pub mod state;
+pub mod storage;
+pub mod timing;
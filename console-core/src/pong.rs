@@ -0,0 +1,1056 @@
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::{DrawTarget, Drawable, Point as ScreenPoint, Primitive, RgbColor, Size};
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::Text;
+use heapless::Vec;
+use oorandom::Rand32;
+use crate::input::{InputSnapshot, JoyToPin, JOY_CENTER_VAL, JOY_LOWER_BOUND, JOY_MAX_VAL, JOY_UPPER_BOUND};
+use crate::pong_ai::{CpuPaddle, CpuParams};
+use crate::pong_arcade::{PowerUp, PowerUpKind};
+use crate::render::Render;
+
+pub const PLAYER_SIZE: i16 = 4;
+/// The ball is a square this many pixels wide
+pub const BALL_SIZE: i16 = 2;
+pub const PLAYER_MOVE_DELTA: i16 = 2;
+/// Stick deflection ignored by `StickMode::Proportional`, about 1/8 of the range
+const DEAD_ZONE: i32 = 256;
+/// Paddle speed at full deflection in `StickMode::Proportional`, in 1/256 pixels per tick
+const MAX_PROPORTIONAL_SPEED: i32 = 3 * 256;
+/// One ball step every 20 ms
+pub const TICK_PERIOD_US: u32 = 20_000;
+
+/// Ball coordinates and speeds are fixed point with this many fraction bits
+pub const FRACTION_BITS: u32 = 8;
+/// One pixel in fixed point
+pub const ONE: i32 = 1 << FRACTION_BITS;
+/// Horizontal speed after a serve, one pixel per tick
+pub const SERVE_SPEED: i32 = ONE;
+pub const MAX_SPEED: i32 = 3 * ONE;
+/// Added to the horizontal speed every `RETURNS_PER_SPEEDUP` returns
+pub const SPEEDUP: i32 = ONE / 8;
+pub const RETURNS_PER_SPEEDUP: u8 = 4;
+/// Vertical speed of a ball hitting the very end of a paddle, in quarters
+/// of the horizontal speed
+pub const EDGE_ANGLE_QUARTERS: i32 = 3;
+/// Steeper than this a rally would only bounce between the walls
+const MAX_SLOPE: i32 = 2;
+/// Part of the paddle's own speed passed on to the ball at contact
+const PADDLE_CARRY_SHIFT: u32 = 2;
+/// Spin from a moving paddle bends the path by `spin` per tick and loses
+/// 1/2^`SPIN_DECAY_SHIFT` of its strength every tick
+const SPIN_SHIFT: u32 = 5;
+const SPIN_DECAY_SHIFT: u32 = 3;
+/// A tick can bounce off a wall, a paddle and the other wall, more would
+/// mean the ball is stuck in a corner
+pub const MAX_CONTACTS_PER_TICK: usize = 4;
+/// Balls in play at once in arcade mode
+pub const MAX_BALLS: usize = 3;
+/// Half lengths of a paddle grown or shrunk by a power-up
+const LONG_PLAYER_SIZE: i16 = 7;
+const SHORT_PLAYER_SIZE: i16 = 2;
+/// Ticks a paddle keeps its power-up size, 10 s
+const PADDLE_EFFECT_TICKS: u16 = 500;
+/// Ticks before a power-up shows up, after the start or after the last one
+/// was taken, 5 s
+const POWER_UP_INTERVAL: u16 = 250;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayerTurn {
+    Player1 = 0,
+    Player2 = 1
+}
+
+impl PlayerTurn {
+    pub fn opponent(self) -> PlayerTurn {
+        match self {
+            PlayerTurn::Player1 => PlayerTurn::Player2,
+            PlayerTurn::Player2 => PlayerTurn::Player1,
+        }
+    }
+}
+
+/// How a stick moves its paddle
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StickMode {
+    /// Fixed speed once the stick is pushed past a quarter of its travel
+    #[default]
+    Digital,
+    /// Speed grows with the deflection past a small dead zone
+    Proportional,
+    /// The paddle sits where the stick points
+    Absolute,
+}
+
+impl StickMode {
+    pub const ALL: [StickMode; 3] = [StickMode::Digital, StickMode::Proportional, StickMode::Absolute];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StickMode::Digital => "Digital",
+            StickMode::Proportional => "Analog",
+            StickMode::Absolute => "Absolute",
+        }
+    }
+}
+
+/// Something the ball runs into during a tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Contact {
+    /// Top or bottom wall, at this `y`
+    Wall(i32),
+    /// The plane of a paddle's face, which the ball may still miss
+    Paddle(PlayerTurn),
+}
+
+/// Match rules, picked on the settings screen before the match
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PongConfig {
+    /// Points that win a set
+    pub target_score: u8,
+    /// A set also needs a two point lead, play goes on past `target_score`
+    pub win_by_two: bool,
+    /// Best of this many sets, odd so there is always a winner
+    pub sets: u8,
+    /// Players take turns serving instead of the ball going to a random side
+    pub alternate_serve: bool,
+    /// Power-ups and extra balls, see `pong_arcade`
+    pub arcade: bool,
+}
+
+impl PongConfig {
+    pub const TARGET_SCORES: [u8; 5] = [5, 7, 11, 15, 21];
+    pub const SET_COUNTS: [u8; 3] = [1, 3, 5];
+
+    /// Sets a player needs to win the match
+    pub fn sets_to_win(&self) -> u8 {
+        self.sets / 2 + 1
+    }
+}
+
+impl Default for PongConfig {
+    fn default() -> Self {
+        PongConfig { target_score: 11, win_by_two: false, sets: 1, alternate_serve: false, arcade: false }
+    }
+}
+
+/// What moves a paddle
+pub enum Controller {
+    /// The player's vertical stick
+    Stick(StickMode),
+    Cpu(CpuPaddle),
+}
+
+pub struct Point {
+    pub x: i16,
+    pub y: i16
+}
+
+/// Ball position (its top left corner) and velocity in fixed point, see `ONE`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ball {
+    pub x: i32,
+    pub y: i32,
+    pub dx: i32,
+    pub dy: i32,
+    /// Change of `dy` per tick, from a paddle that was moving at contact
+    pub spin: i32,
+    /// Horizontal speed, it grows during a rally
+    pub speed: i32,
+    /// Returns since the ball was served
+    pub returns: u8,
+}
+
+impl Ball {
+    /// Resting ball at pixel `x`, `y`
+    pub fn at(x: i16, y: i16) -> Self {
+        Ball { x: x as i32 * ONE, y: y as i32 * ONE, dx: 0, dy: 0, spin: 0, speed: 0, returns: 0 }
+    }
+
+    /// Also sets the speed to that of `dx`
+    pub fn with_velocity(mut self, dx: i32, dy: i32) -> Self {
+        self.dx = dx;
+        self.dy = dy;
+        self.speed = dx.abs();
+        self
+    }
+
+    /// Pixel the ball is drawn at
+    pub fn position(&self) -> Point {
+        Point { x: (self.x >> FRACTION_BITS) as i16, y: (self.y >> FRACTION_BITS) as i16 }
+    }
+
+    /// Moves the ball on by `time` in 1/ONE of a tick
+    pub fn advance(&mut self, time: i32) {
+        self.x += self.dx * time / ONE;
+        self.y += self.dy * time / ONE;
+    }
+}
+
+pub struct Pong {
+    pub width: i16,
+    pub height: i16,
+    /// Always at least one, more only in arcade mode
+    pub balls: Vec<Ball, MAX_BALLS>,
+    pub player1: i16,
+    pub player2: i16,
+    /// Half length of each paddle, `PLAYER_SIZE` unless a power-up changed it
+    pub paddle_sizes: [i16; 2],
+    /// Points in the current set
+    pub player1_score: u8,
+    pub player2_score: u8,
+    pub player1_sets: u8,
+    pub player2_sets: u8,
+    pub config: PongConfig,
+    /// Serves next when `PongConfig::alternate_serve` is on
+    pub server: PlayerTurn,
+    pub power_up: Option<PowerUp>,
+    pub is_running: bool,
+    pub rng: Rand32,
+    /// Indexed by `PlayerTurn`
+    pub controllers: [Controller; 2],
+    /// Sub-pixel paddle movement left over from `StickMode::Proportional`,
+    /// in 1/256 pixels
+    paddle_carry: [i32; 2],
+    /// How far each paddle moved this tick, for spin
+    paddle_motion: [i16; 2],
+    /// Ticks until each paddle is back to `PLAYER_SIZE`
+    paddle_effect_ticks: [u16; 2],
+    /// Ticks until the next power-up shows up
+    power_up_ticks: u16,
+    /// Ball each computer paddle is playing, see `ball_to_watch`
+    watched_balls: [usize; 2]
+}
+
+impl Pong {
+    pub fn new(width: i16, height: i16, seed: u64) -> Self {
+        Self::with_config(width, height, seed, PongConfig::default())
+    }
+
+    pub fn with_config(width: i16, height: i16, seed: u64, config: PongConfig) -> Self {
+        let mut pong = Pong{
+            width,
+            height,
+            balls: Vec::new(),
+            player1: height / 2,
+            player2: height / 2,
+            paddle_sizes: [PLAYER_SIZE; 2],
+            player1_score: 0,
+            player2_score: 0,
+            player1_sets: 0,
+            player2_sets: 0,
+            config,
+            server: PlayerTurn::Player1,
+            power_up: None,
+            is_running: true,
+            rng: Rand32::new(seed),
+            controllers: [Controller::Stick(StickMode::Digital), Controller::Stick(StickMode::Digital)],
+            paddle_carry: [0; 2],
+            paddle_motion: [0; 2],
+            paddle_effect_ticks: [0; 2],
+            power_up_ticks: POWER_UP_INTERVAL,
+            watched_balls: [0; 2]
+        };
+        pong.spawn_ball();
+        pong
+    }
+
+    /// Hands a paddle to the computer, its misses are seeded from `seed`
+    pub fn with_cpu(mut self, which_player: PlayerTurn, params: CpuParams, seed: u64) -> Self {
+        self.controllers[which_player as usize] = Controller::Cpu(CpuPaddle::new(params, seed));
+        self
+    }
+
+    pub fn with_stick_mode(mut self, which_player: PlayerTurn, mode: StickMode) -> Self {
+        self.controllers[which_player as usize] = Controller::Stick(mode);
+        self
+    }
+
+    pub fn is_cpu(&self, which_player: PlayerTurn) -> bool {
+        matches!(self.controllers[which_player as usize], Controller::Cpu(_))
+    }
+
+    pub fn move_player(&mut self, which_player: PlayerTurn, value: i16) {
+        let dy = if value > JOY_UPPER_BOUND as i16 { PLAYER_MOVE_DELTA } else if value < JOY_LOWER_BOUND as i16 { -PLAYER_MOVE_DELTA } else { 0 };
+        self.move_paddle(which_player, dy);
+    }
+
+    /// Speed follows the deflection, slow moves add up over several ticks
+    pub fn move_player_proportional(&mut self, which_player: PlayerTurn, value: u16) {
+        let deflection = value as i32 - JOY_CENTER_VAL as i32;
+        let speed = if deflection.abs() < DEAD_ZONE {
+            0
+        } else {
+            let travel = deflection.abs() - DEAD_ZONE;
+            deflection.signum() * travel * MAX_PROPORTIONAL_SPEED / (JOY_CENTER_VAL as i32 - DEAD_ZONE)
+        };
+
+        let carry = &mut self.paddle_carry[which_player as usize];
+        let total = *carry + speed;
+        *carry = total % 256;
+        self.move_paddle(which_player, (total / 256) as i16);
+    }
+
+    /// The full stick travel spans the height the paddle can move in
+    pub fn place_player(&mut self, which_player: PlayerTurn, value: u16) {
+        let size = self.paddle_sizes[which_player as usize];
+        let range = (self.height - 2 * size) as i32;
+        let y = size + (value.min(JOY_MAX_VAL) as i32 * range / JOY_MAX_VAL as i32) as i16;
+        let paddle = self.paddle(which_player);
+        self.move_paddle(which_player, y - paddle);
+    }
+
+    pub fn move_paddle(&mut self, which_player: PlayerTurn, dy: i16) {
+        let size = self.paddle_sizes[which_player as usize];
+        let paddle = match which_player {
+            PlayerTurn::Player1 => &mut self.player1,
+            PlayerTurn::Player2 => &mut self.player2,
+        };
+        let moved = (*paddle + dy).clamp(size, self.height - size);
+        self.paddle_motion[which_player as usize] = moved - *paddle;
+        *paddle = moved;
+    }
+
+    fn paddle(&self, which_player: PlayerTurn) -> i16 {
+        match which_player {
+            PlayerTurn::Player1 => self.player1,
+            PlayerTurn::Player2 => self.player2,
+        }
+    }
+
+    /// Left paddle follows the first stick, right paddle the second one,
+    /// unless the computer plays it
+    pub fn handle_input(&mut self, input: &InputSnapshot) {
+        self.control_player(PlayerTurn::Player1, input.axis(JoyToPin::JoyY1));
+        self.control_player(PlayerTurn::Player2, input.axis(JoyToPin::JoyY2));
+    }
+
+    fn control_player(&mut self, which_player: PlayerTurn, stick: u16) {
+        let paddle_x = (self.contact_x(which_player) >> FRACTION_BITS) as i16;
+        let paddle = self.paddle(which_player);
+        let watched = self.ball_to_watch(paddle_x);
+
+        match &mut self.controllers[which_player as usize] {
+            Controller::Stick(StickMode::Digital) => self.move_player(which_player, stick as i16),
+            Controller::Stick(StickMode::Proportional) => self.move_player_proportional(which_player, stick),
+            Controller::Stick(StickMode::Absolute) => self.place_player(which_player, stick),
+            Controller::Cpu(cpu) => {
+                if self.watched_balls[which_player as usize] != watched {
+                    self.watched_balls[which_player as usize] = watched;
+                    cpu.follow_new_ball();
+                }
+                let dy = cpu.step(&self.balls[watched], paddle_x, paddle, self.height);
+                self.move_paddle(which_player, dy);
+            }
+        }
+    }
+
+    /// Index of the ball that reaches column `paddle_x` first, or of the
+    /// first ball when all of them are heading away
+    fn ball_to_watch(&self, paddle_x: i16) -> usize {
+        let x = paddle_x as i32 * ONE;
+        self.balls
+            .iter()
+            .enumerate()
+            .filter(|(_, ball)| ball.dx != 0 && (ball.dx > 0) == (x > ball.x))
+            .min_by_key(|(_, ball)| (x - ball.x) / ball.dx)
+            .map_or(0, |(index, _)| index)
+    }
+
+    pub fn score(&mut self, which_player: PlayerTurn) {
+        match which_player {
+            PlayerTurn::Player1 => self.player1_score += 1,
+            PlayerTurn::Player2 => self.player2_score += 1
+        }
+    }
+
+    /// Ends the set once a player has reached the target score (with a two
+    /// point lead if the rules ask for it) and the match once a player has
+    /// won enough sets. The score of the last set stays up at the end.
+    pub fn check_for_win(&mut self) {
+        if !self.is_running {
+            return;
+        }
+        let lead = if self.config.win_by_two { 2 } else { 1 };
+        let wins = |score: u8, other: u8| score >= self.config.target_score && score >= other + lead;
+
+        if wins(self.player1_score, self.player2_score) {
+            self.player1_sets += 1;
+        } else if wins(self.player2_score, self.player1_score) {
+            self.player2_sets += 1;
+        } else {
+            return;
+        }
+
+        if self.player1_sets.max(self.player2_sets) >= self.config.sets_to_win() {
+            self.is_running = false;
+        } else {
+            self.player1_score = 0;
+            self.player2_score = 0;
+            self.spawn_ball();
+        }
+    }
+
+    /// A single ball back in the middle, towards the player who is not
+    /// serving or to a random side
+    pub fn spawn_ball(&mut self) {
+        let to_player2 = if self.config.alternate_serve {
+            let server = self.server;
+            self.server = server.opponent();
+            server == PlayerTurn::Player1
+        } else {
+            self.rng.rand_range(0..2) == 0
+        };
+        let ball = self.serve(to_player2);
+        self.balls.clear();
+        let _ = self.balls.push(ball);
+    }
+
+    /// New ball in the middle at serve speed and a random angle
+    fn serve(&mut self, to_player2: bool) -> Ball {
+        let dx = if to_player2 { SERVE_SPEED } else { -SERVE_SPEED };
+        let dy = self.rng.rand_range(0..SERVE_SPEED as u32 + 1) as i32 - SERVE_SPEED / 2;
+        Ball::at(self.width / 2, self.height / 2).with_velocity(dx, dy)
+    }
+
+    /// Moves every ball through one tick. A ball that gets past a paddle
+    /// scores; the last one is served again, extra ones leave play.
+    pub fn update_balls(&mut self) {
+        let mut index = 0;
+        while index < self.balls.len() {
+            match self.update_ball(index) {
+                Some(scorer) => {
+                    self.score(scorer);
+                    if self.balls.len() > 1 {
+                        self.balls.swap_remove(index);
+                        continue;
+                    }
+                    self.spawn_ball();
+                }
+                None => self.collect_power_up(index),
+            }
+            index += 1;
+        }
+    }
+
+    /// Moves a ball through one tick and returns who scores if it got past
+    /// a paddle. The tick is walked from one contact to the next, so at any
+    /// speed the ball bounces off everything on its path instead of skipping
+    /// past it, and it never leaves the screen.
+    fn update_ball(&mut self, index: usize) -> Option<PlayerTurn> {
+        let ball = &mut self.balls[index];
+        ball.dy = (ball.dy + ball.spin).clamp(-MAX_SLOPE * ball.speed, MAX_SLOPE * ball.speed);
+        ball.spin -= ball.spin >> SPIN_DECAY_SHIFT;
+
+        // Time is in 1/ONE of a tick
+        let mut remaining = ONE;
+        for _ in 0..MAX_CONTACTS_PER_TICK {
+            let (time, contact) = match self.next_contact(&self.balls[index], remaining) {
+                Some(next) => next,
+                None => break,
+            };
+            self.balls[index].advance(time);
+            remaining -= time;
+
+            match contact {
+                Contact::Wall(y) => {
+                    // Mirrored back in, the spin turns with it
+                    let ball = &mut self.balls[index];
+                    ball.y = y;
+                    ball.dy = -ball.dy;
+                    ball.spin = -ball.spin;
+                }
+                Contact::Paddle(which_player) => {
+                    self.balls[index].x = self.contact_x(which_player);
+                    self.hit_paddle(index, which_player);
+                }
+            }
+        }
+        let ball = &mut self.balls[index];
+        ball.advance(remaining);
+
+        // Past a paddle the ball only runs on to the edge of the screen
+        if ball.x <= 0 {
+            Some(PlayerTurn::Player2)
+        } else if ball.x >= (self.width - BALL_SIZE) as i32 * ONE {
+            Some(PlayerTurn::Player1)
+        } else {
+            None
+        }
+    }
+
+    /// `x` of the ball when it touches the face of a paddle
+    fn contact_x(&self, which_player: PlayerTurn) -> i32 {
+        match which_player {
+            // The left paddle is the first column
+            PlayerTurn::Player1 => ONE,
+            // The right paddle is the second to last column
+            PlayerTurn::Player2 => (self.width - 2 - BALL_SIZE) as i32 * ONE,
+        }
+    }
+
+    /// The first wall or paddle face `ball` reaches within `remaining`, walls
+    /// first when both are reached at once
+    fn next_contact(&self, ball: &Ball, remaining: i32) -> Option<(i32, Contact)> {
+        let bottom = (self.height - BALL_SIZE) as i32 * ONE;
+        let mut next: Option<(i32, Contact)> = None;
+
+        let wall = if ball.dy < 0 {
+            Some((ball.y * ONE / -ball.dy, Contact::Wall(0)))
+        } else if ball.dy > 0 {
+            Some(((bottom - ball.y) * ONE / ball.dy, Contact::Wall(bottom)))
+        } else {
+            None
+        };
+
+        let left = self.contact_x(PlayerTurn::Player1);
+        let right = self.contact_x(PlayerTurn::Player2);
+        let paddle = if ball.dx < 0 && ball.x > left {
+            Some(((ball.x - left) * ONE / -ball.dx, Contact::Paddle(PlayerTurn::Player1)))
+        } else if ball.dx > 0 && ball.x < right {
+            Some(((right - ball.x) * ONE / ball.dx, Contact::Paddle(PlayerTurn::Player2)))
+        } else {
+            None
+        };
+
+        for (time, contact) in [wall, paddle].into_iter().flatten() {
+            if time <= remaining && next.map_or(true, |(first, _)| time < first) {
+                next = Some((time, contact));
+            }
+        }
+        next
+    }
+
+    /// Sends the ball back if it overlaps the paddle. The further from the
+    /// paddle's centre it lands the steeper it leaves, and a moving paddle
+    /// drags it along and gives it spin.
+    fn hit_paddle(&mut self, index: usize, which_player: PlayerTurn) {
+        let size = self.paddle_sizes[which_player as usize];
+        let motion = self.paddle_motion[which_player as usize] as i32 * ONE;
+        let centre = self.paddle(which_player) as i32 * ONE;
+        let ball = &mut self.balls[index];
+
+        let offset = ball.y + (BALL_SIZE / 2) as i32 * ONE - centre;
+        // A ball clipping the end of the paddle with its corner still counts
+        if offset.abs() >= (size + BALL_SIZE / 2) as i32 * ONE {
+            return;
+        }
+
+        ball.returns += 1;
+        if ball.returns % RETURNS_PER_SPEEDUP == 0 {
+            ball.speed = (ball.speed + SPEEDUP).min(MAX_SPEED);
+        }
+
+        let reach = size as i32 * ONE;
+        let offset = offset.clamp(-reach, reach);
+        ball.dx = if ball.dx < 0 { ball.speed } else { -ball.speed };
+        ball.dy = offset * ball.speed * EDGE_ANGLE_QUARTERS / (4 * reach) + (motion >> PADDLE_CARRY_SHIFT);
+        ball.spin = motion >> SPIN_SHIFT;
+    }
+
+    /// Counts down the power-up timers: paddles go back to their size and a
+    /// new power-up shows up when it is time
+    fn update_power_ups(&mut self) {
+        for which_player in [PlayerTurn::Player1, PlayerTurn::Player2] {
+            let ticks = &mut self.paddle_effect_ticks[which_player as usize];
+            if *ticks > 0 {
+                *ticks -= 1;
+                if *ticks == 0 {
+                    self.resize_paddle(which_player, PLAYER_SIZE);
+                }
+            }
+        }
+
+        if self.power_up.is_none() {
+            self.power_up_ticks = self.power_up_ticks.saturating_sub(1);
+            if self.power_up_ticks == 0 {
+                self.power_up = Some(PowerUp::random(&mut self.rng, self.width, self.height));
+            }
+        }
+    }
+
+    /// Gives the power-up to the player who sent ball `index` if the ball
+    /// runs into it
+    fn collect_power_up(&mut self, index: usize) {
+        let power_up = match self.power_up {
+            Some(power_up) if power_up.touches(&self.balls[index]) => power_up,
+            _ => return,
+        };
+        self.power_up = None;
+        self.power_up_ticks = POWER_UP_INTERVAL;
+
+        let collector = if self.balls[index].dx > 0 { PlayerTurn::Player1 } else { PlayerTurn::Player2 };
+        match power_up.kind {
+            PowerUpKind::LongPaddle => self.resize_paddle(collector, LONG_PLAYER_SIZE),
+            PowerUpKind::ShrinkOpponent => self.resize_paddle(collector.opponent(), SHORT_PLAYER_SIZE),
+            PowerUpKind::FastBall => {
+                let ball = &mut self.balls[index];
+                ball.speed = (ball.speed + ball.speed / 2).min(MAX_SPEED);
+                ball.dx = ball.dx.signum() * ball.speed;
+            }
+            PowerUpKind::ExtraBall => {
+                let ball = self.serve(collector == PlayerTurn::Player1);
+                let _ = self.balls.push(ball);
+            }
+        }
+    }
+
+    /// Changes the half length of a paddle, for `PADDLE_EFFECT_TICKS` unless
+    /// it goes back to `PLAYER_SIZE`
+    fn resize_paddle(&mut self, which_player: PlayerTurn, size: i16) {
+        self.paddle_sizes[which_player as usize] = size;
+        self.paddle_effect_ticks[which_player as usize] = if size == PLAYER_SIZE { 0 } else { PADDLE_EFFECT_TICKS };
+        // Keeps the whole paddle on screen
+        self.move_paddle(which_player, 0);
+    }
+
+    /// One game tick: power-ups, paddles, balls, then the win check
+    pub fn update(&mut self, input: &InputSnapshot) {
+        if self.config.arcade {
+            self.update_power_ups();
+        }
+        self.handle_input(input);
+        self.update_balls();
+        self.check_for_win();
+    }
+
+}
+
+impl Render for Pong {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let paddle_style = PrimitiveStyle::with_fill(Rgb565::WHITE);
+        let ball_style = PrimitiveStyle::with_fill(Rgb565::RED);
+        let paddle_size = |which_player: PlayerTurn| Size::new(1, (self.paddle_sizes[which_player as usize] * 2) as u32);
+        let [size1, size2] = self.paddle_sizes;
+
+        Rectangle::new(ScreenPoint::new(0, (self.player1 - size1) as i32), paddle_size(PlayerTurn::Player1))
+            .into_styled(paddle_style)
+            .draw(target)?;
+
+        Rectangle::new(ScreenPoint::new(self.width as i32 - 2, (self.player2 - size2) as i32), paddle_size(PlayerTurn::Player2))
+            .into_styled(paddle_style)
+            .draw(target)?;
+
+        for ball in &self.balls {
+            let ball = ball.position();
+            Rectangle::new(ScreenPoint::new(ball.x as i32, ball.y as i32), Size::new(BALL_SIZE as u32, BALL_SIZE as u32))
+                .into_styled(ball_style)
+                .draw(target)?;
+        }
+
+        if let Some(power_up) = self.power_up {
+            let size = PowerUp::SIZE as u32;
+            Rectangle::new(ScreenPoint::new(power_up.x as i32, power_up.y as i32), Size::new(size, size))
+                .into_styled(PrimitiveStyle::with_fill(power_up.kind.color()))
+                .draw(target)?;
+        }
+
+        let mut buf1 = itoa::Buffer::new();
+        let mut buf2 = itoa::Buffer::new();
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        Text::new(buf1.format(self.player1_score), ScreenPoint::new(70, 20), style).draw(target)?;
+        Text::new(buf2.format(self.player2_score), ScreenPoint::new(90, 20), style).draw(target)?;
+
+        if self.config.sets > 1 {
+            let sets_style = MonoTextStyle::new(&FONT_6X10, Rgb565::YELLOW);
+            Text::new(buf1.format(self.player1_sets), ScreenPoint::new(52, 20), sets_style).draw(target)?;
+            Text::new(buf2.format(self.player2_sets), ScreenPoint::new(108, 20), sets_style).draw(target)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+    use crate::input::{InputSource, ScriptedInput, JOY_MAX_VAL};
+    use crate::pong_ai::Difficulty;
+
+    #[test]
+    fn paddles_follow_their_own_stick() {
+        let frames = [
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL),
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY2, 0),
+        ];
+        let mut input = ScriptedInput::new(&frames);
+        let mut pong = Pong::new(160, 128, 1);
+
+        pong.handle_input(&input.read());
+        assert_eq!(pong.player1, 64 + PLAYER_MOVE_DELTA);
+        assert_eq!(pong.player2, 64);
+
+        pong.handle_input(&input.read());
+        assert_eq!(pong.player1, 64 + PLAYER_MOVE_DELTA);
+        assert_eq!(pong.player2, 64 - PLAYER_MOVE_DELTA);
+    }
+
+    #[test]
+    fn render_draws_paddles_ball_and_score() {
+        let mut pong = Pong::new(160, 128, 1);
+        pong.player1 = 20;
+        pong.balls[0] = Ball::at(40, 50);
+        pong.player2_score = 7;
+
+        let mut framebuffer: Framebuffer = Framebuffer::new();
+        pong.render(&mut framebuffer).unwrap();
+
+        // Left paddle covers PLAYER_SIZE pixels on both sides of its centre
+        for y in 0..128 {
+            let expected = if (16..24).contains(&y) { Rgb565::WHITE } else { Rgb565::BLACK };
+            assert_eq!(framebuffer.pixel(0, y), Some(expected), "left paddle at y = {}", y);
+        }
+        assert_eq!(framebuffer.pixel(158, 60), Some(Rgb565::WHITE));
+        assert_eq!(framebuffer.pixel(159, 64), Some(Rgb565::BLACK));
+
+        for (x, y) in [(40, 50), (41, 50), (40, 51), (41, 51)] {
+            assert_eq!(framebuffer.pixel(x, y), Some(Rgb565::RED));
+        }
+        assert_eq!(framebuffer.pixel(42, 50), Some(Rgb565::BLACK));
+
+        let lit_in = |x0: i32| (x0..x0 + 6).flat_map(|x| (11..21).map(move |y| (x, y)))
+            .filter(|&(x, y)| framebuffer.pixel(x, y) == Some(Rgb565::WHITE))
+            .count();
+        assert!(lit_in(70) > 0, "player 1 score missing");
+        assert!(lit_in(90) > 0, "player 2 score missing");
+        assert!(lit_in(80) == 0);
+    }
+
+    #[test]
+    fn cpu_paddle_ignores_its_stick() {
+        let params = CpuParams { reaction_ticks: 0, max_speed: 2, prediction_error: 0 };
+        let mut pong = Pong::new(160, 128, 1).with_cpu(PlayerTurn::Player2, params, 9);
+        assert!(pong.is_cpu(PlayerTurn::Player2) && !pong.is_cpu(PlayerTurn::Player1));
+        pong.balls[0] = Ball::at(100, 64).with_velocity(ONE, ONE);
+
+        // Ball reaches the paddle at y = 120, the paddle heads down whatever the stick says
+        pong.handle_input(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY2, 0));
+        assert_eq!(pong.player2, 64 + 2);
+    }
+
+    #[test]
+    fn cpu_returns_most_balls() {
+        let mut pong = Pong::new(160, 128, 4)
+            .with_cpu(PlayerTurn::Player1, Difficulty::Hard.params(), 1)
+            .with_cpu(PlayerTurn::Player2, Difficulty::Hard.params(), 2);
+
+        for _ in 0..5_000 {
+            pong.update(&InputSnapshot::CENTERED);
+        }
+        assert!(pong.player1_score + pong.player2_score < 4);
+    }
+
+    #[test]
+    fn proportional_speed_follows_deflection() {
+        let mut pong = Pong::new(160, 128, 1).with_stick_mode(PlayerTurn::Player1, StickMode::Proportional);
+        let stick = |value| InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, value);
+
+        // Inside the dead zone nothing moves, however long it is held
+        for _ in 0..50 {
+            pong.handle_input(&stick(JOY_CENTER_VAL + 200));
+        }
+        assert_eq!(pong.player1, 64);
+
+        // Full deflection is 3 pixels a tick, half of it a bit more than 1
+        for _ in 0..4 {
+            pong.handle_input(&stick(JOY_MAX_VAL));
+        }
+        assert_eq!(pong.player1, 64 + 11);
+
+        for _ in 0..4 {
+            pong.handle_input(&stick(JOY_CENTER_VAL - 1100));
+        }
+        assert_eq!(pong.player1, 64 + 11 - 4);
+    }
+
+    #[test]
+    fn absolute_stick_places_paddle() {
+        let mut pong = Pong::new(160, 128, 1).with_stick_mode(PlayerTurn::Player2, StickMode::Absolute);
+
+        pong.handle_input(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY2, 0));
+        assert_eq!(pong.player2, PLAYER_SIZE);
+        pong.handle_input(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY2, JOY_MAX_VAL));
+        assert_eq!(pong.player2, 128 - PLAYER_SIZE);
+        pong.handle_input(&InputSnapshot::CENTERED);
+        assert_eq!(pong.player2, 64);
+        assert_eq!(pong.player1, 64);
+    }
+
+    /// Ball centred on `y`, reaching the left paddle within one tick, it
+    /// keeps its speed and count of returns
+    fn ball_reaching_left(pong: &mut Pong, y: i16) {
+        let ball = &mut pong.balls[0];
+        *ball = Ball { speed: ball.speed, returns: ball.returns, ..Ball::at(2, y - BALL_SIZE / 2) }
+            .with_velocity(-ball.speed, 0);
+    }
+
+    #[test]
+    fn bounce_angle_follows_hit_point() {
+        let mut pong = Pong::new(160, 128, 1);
+        pong.player1 = 64;
+
+        ball_reaching_left(&mut pong, 64);
+        pong.update_balls();
+        assert_eq!((pong.balls[0].dx, pong.balls[0].dy), (ONE, 0));
+
+        // The far end of the paddle sends it off at 3/4 of its speed
+        ball_reaching_left(&mut pong, 64 + PLAYER_SIZE);
+        pong.update_balls();
+        assert_eq!(pong.balls[0].dy, 3 * ONE / 4);
+        ball_reaching_left(&mut pong, 64 - PLAYER_SIZE / 2);
+        pong.update_balls();
+        assert_eq!(pong.balls[0].dy, -3 * ONE / 8);
+
+        // Clear of the end it is a miss
+        ball_reaching_left(&mut pong, 64 + PLAYER_SIZE + BALL_SIZE / 2);
+        pong.update_balls();
+        pong.update_balls();
+        assert_eq!(pong.player2_score, 1);
+        assert_eq!(pong.balls[0].speed, SERVE_SPEED);
+    }
+
+    #[test]
+    fn rallies_speed_up() {
+        let mut pong = Pong::new(160, 128, 1);
+        for returns in 1..=RETURNS_PER_SPEEDUP * 2 {
+            ball_reaching_left(&mut pong, 64);
+            pong.update_balls();
+            assert_eq!(pong.balls[0].returns, returns);
+        }
+        assert_eq!(pong.balls[0].speed, SERVE_SPEED + 2 * SPEEDUP);
+        assert_eq!(pong.balls[0].dx, pong.balls[0].speed);
+    }
+
+    #[test]
+    fn moving_paddle_gives_spin() {
+        let mut pong = Pong::new(160, 128, 1);
+        pong.handle_input(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL));
+        let paddle = pong.player1;
+        ball_reaching_left(&mut pong, paddle);
+        pong.update_balls();
+
+        // Dragged along with the paddle, then curving further the same way
+        assert_eq!(pong.balls[0].dy, 2 * ONE / 4);
+        assert!(pong.balls[0].spin > 0);
+        let dy = pong.balls[0].dy;
+        pong.update_balls();
+        assert!(pong.balls[0].dy > dy);
+    }
+
+    #[test]
+    fn same_seed_same_rally() {
+        let play = |seed| {
+            let mut pong = Pong::new(160, 128, seed)
+                .with_cpu(PlayerTurn::Player1, Difficulty::Normal.params(), seed)
+                .with_cpu(PlayerTurn::Player2, Difficulty::Normal.params(), seed + 1);
+            for _ in 0..3_000 {
+                pong.update(&InputSnapshot::CENTERED);
+            }
+            (pong.balls[0], pong.player1_score, pong.player2_score)
+        };
+
+        assert_eq!(play(7), play(7));
+        assert_ne!(play(7), play(8));
+    }
+
+    #[test]
+    fn fast_ball_does_not_tunnel() {
+        for speed in [ONE, 3 * ONE, 7 * ONE + 77, 40 * ONE, 150 * ONE] {
+            let mut pong = Pong::new(160, 128, 1);
+            pong.player2 = 40;
+            pong.balls[0] = Ball::at(80, 39).with_velocity(speed, 0);
+
+            while pong.balls[0].dx > 0 && pong.player1_score == 0 {
+                pong.update_balls();
+            }
+            assert_eq!(pong.balls[0].returns, 1, "speed {} went through the paddle", speed);
+            assert_eq!(pong.player1_score, 0);
+            assert!(pong.balls[0].x <= pong.contact_x(PlayerTurn::Player2));
+        }
+    }
+
+    #[test]
+    fn corner_hit_bounces_off_wall_then_paddle() {
+        let mut pong = Pong::new(160, 128, 1);
+        pong.player1 = PLAYER_SIZE;
+
+        // Reaches the top wall after half a tick and the paddle right after
+        pong.balls[0] = Ball::at(3, 1).with_velocity(-3 * ONE, -2 * ONE);
+        pong.update_balls();
+
+        assert_eq!(pong.balls[0].returns, 1);
+        assert!(pong.balls[0].dx > 0 && pong.balls[0].dy > 0);
+        assert!(pong.balls[0].y >= 0 && pong.balls[0].x >= pong.contact_x(PlayerTurn::Player1));
+    }
+
+    #[test]
+    fn paddle_edge_hits_and_misses() {
+        let mut pong = Pong::new(160, 128, 1);
+        pong.player1 = 64;
+        let reach = (PLAYER_SIZE + BALL_SIZE / 2) as i32 * ONE;
+
+        // Only a corner of the ball overlaps the last pixel of the paddle,
+        // it leaves at the steepest angle
+        for (centre, dy) in [(64 * ONE + reach - 1, 3 * ONE / 4), (64 * ONE - reach + 1, -3 * ONE / 4)] {
+            pong.balls[0] = Ball::at(2, 0).with_velocity(-ONE, 0);
+            pong.balls[0].y = centre - ONE;
+            pong.update_balls();
+            assert_eq!((pong.balls[0].dx, pong.balls[0].dy), (ONE, dy));
+        }
+
+        // Touching the end without overlapping is a miss
+        pong.balls[0] = Ball::at(2, 0).with_velocity(-ONE, 0);
+        pong.balls[0].y = 64 * ONE + reach - ONE;
+        pong.update_balls();
+        pong.update_balls();
+        assert_eq!(pong.player2_score, 1);
+    }
+
+    #[test]
+    fn ball_stays_on_screen() {
+        let mut pong = Pong::new(160, 128, 5)
+            .with_cpu(PlayerTurn::Player1, Difficulty::Easy.params(), 1)
+            .with_cpu(PlayerTurn::Player2, Difficulty::Hard.params(), 2);
+        pong.balls[0].speed = MAX_SPEED;
+
+        for _ in 0..5_000 {
+            pong.update(&InputSnapshot::CENTERED);
+            let ball = pong.balls[0].position();
+            assert!((0..=160 - BALL_SIZE).contains(&ball.x), "ball at x = {}", ball.x);
+            assert!((0..=128 - BALL_SIZE).contains(&ball.y), "ball at y = {}", ball.y);
+        }
+        assert!(pong.player1_score + pong.player2_score > 0);
+    }
+
+    #[test]
+    fn sets_need_target_and_lead() {
+        let config = PongConfig { target_score: 5, win_by_two: true, sets: 3, ..PongConfig::default() };
+        let mut pong = Pong::with_config(160, 128, 1, config);
+
+        // 5 - 4 is not enough with win by two
+        pong.player1_score = 5;
+        pong.player2_score = 4;
+        pong.check_for_win();
+        assert_eq!((pong.player1_sets, pong.player1_score), (0, 5));
+
+        pong.player2_score = 3;
+        pong.check_for_win();
+        assert_eq!((pong.player1_sets, pong.player2_sets), (1, 0));
+        assert_eq!((pong.player1_score, pong.player2_score), (0, 0));
+        assert!(pong.is_running);
+
+        pong.player2_score = 7;
+        pong.player1_score = 5;
+        pong.check_for_win();
+        assert_eq!((pong.player1_sets, pong.player2_sets), (1, 1));
+
+        pong.player1_score = 12;
+        pong.player2_score = 10;
+        pong.check_for_win();
+        assert_eq!((pong.player1_sets, pong.player2_sets), (2, 1));
+        assert!(!pong.is_running);
+        assert_eq!((pong.player1_score, pong.player2_score), (12, 10));
+    }
+
+    #[test]
+    fn serve_alternates() {
+        let config = PongConfig { alternate_serve: true, ..PongConfig::default() };
+        let mut pong = Pong::with_config(160, 128, 1, config);
+
+        let mut towards_player2 = true;
+        for _ in 0..6 {
+            assert_eq!(pong.balls[0].dx > 0, towards_player2);
+            pong.spawn_ball();
+            towards_player2 = !towards_player2;
+        }
+    }
+
+    fn arcade() -> Pong {
+        Pong::with_config(160, 128, 1, PongConfig { arcade: true, ..PongConfig::default() })
+    }
+
+    #[test]
+    fn power_ups_show_up_after_a_while() {
+        let mut pong = Pong::new(160, 128, 1);
+        for _ in 0..POWER_UP_INTERVAL {
+            pong.update(&InputSnapshot::CENTERED);
+        }
+        assert_eq!(pong.power_up, None);
+
+        let mut pong = arcade();
+        for _ in 1..POWER_UP_INTERVAL {
+            pong.update(&InputSnapshot::CENTERED);
+        }
+        assert_eq!(pong.power_up, None);
+        pong.update(&InputSnapshot::CENTERED);
+        assert!(pong.power_up.is_some());
+    }
+
+    #[test]
+    fn extra_ball_scores_and_leaves_play() {
+        let mut pong = arcade();
+        pong.balls[0] = Ball::at(76, 40).with_velocity(ONE, 0);
+        pong.power_up = Some(PowerUp { kind: PowerUpKind::ExtraBall, x: 78, y: 40 });
+        pong.update_balls();
+
+        // Player 1 sent the ball, the new one goes to player 2 as well
+        assert_eq!(pong.power_up, None);
+        assert_eq!(pong.balls.len(), 2);
+        assert!(pong.balls[1].dx > 0);
+
+        // A ball past the paddle scores and is gone, the other plays on
+        pong.player2 = 100;
+        pong.balls[0] = Ball::at(156, 40).with_velocity(ONE, 0);
+        let other = pong.balls[1];
+        pong.update_balls();
+        pong.update_balls();
+        assert_eq!(pong.player1_score, 1);
+        assert_eq!(pong.balls.len(), 1);
+        assert_eq!(pong.balls[0].x, other.x + 2 * ONE);
+
+        // The last ball is served again
+        pong.balls[0] = Ball::at(156, 40).with_velocity(ONE, 0);
+        pong.update_balls();
+        pong.update_balls();
+        assert_eq!(pong.player1_score, 2);
+        assert_eq!(pong.balls[0].position().x, 80);
+    }
+
+    #[test]
+    fn paddle_power_ups_wear_off() {
+        let mut pong = arcade();
+        pong.player2 = 1;
+        pong.balls[0] = Ball::at(80, 40).with_velocity(-ONE, 0);
+        pong.power_up = Some(PowerUp { kind: PowerUpKind::ShrinkOpponent, x: 78, y: 40 });
+        pong.update_balls();
+        // Sent by player 2, player 1 loses out
+        assert_eq!(pong.paddle_sizes, [SHORT_PLAYER_SIZE, PLAYER_SIZE]);
+
+        pong.balls[0] = Ball::at(80, 40).with_velocity(-ONE, 0);
+        pong.power_up = Some(PowerUp { kind: PowerUpKind::LongPaddle, x: 78, y: 40 });
+        pong.update_balls();
+        assert_eq!(pong.paddle_sizes, [SHORT_PLAYER_SIZE, LONG_PLAYER_SIZE]);
+        // Moved down so the longer paddle fits on screen
+        assert_eq!(pong.player2, LONG_PLAYER_SIZE);
+
+        for _ in 0..PADDLE_EFFECT_TICKS {
+            pong.update_power_ups();
+        }
+        assert_eq!(pong.paddle_sizes, [PLAYER_SIZE; 2]);
+    }
+
+    #[test]
+    fn fast_ball_power_up() {
+        let mut pong = arcade();
+        pong.balls[0] = Ball::at(80, 40).with_velocity(-2 * ONE, 0);
+        pong.power_up = Some(PowerUp { kind: PowerUpKind::FastBall, x: 78, y: 40 });
+        pong.update_balls();
+        assert_eq!((pong.balls[0].speed, pong.balls[0].dx), (3 * ONE, -3 * ONE));
+    }
+}
@@ -0,0 +1,192 @@
+//! Computer player for either Pong paddle.
+//!
+//! It only sees what a player sees: where the ball is and where it is going.
+//! Once the ball turns towards its side it waits out its reaction delay, works
+//! out where the ball will cross its paddle (off by up to `prediction_error`,
+//! and blind to spin) and moves there at no more than `max_speed`. While the
+//! ball travels away it drifts back to the middle.
+
+use oorandom::Rand32;
+
+use crate::pong::{Ball, BALL_SIZE, FRACTION_BITS, ONE};
+
+/// How a computer paddle plays
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuParams {
+    /// Ticks between the ball turning and the paddle reacting to it
+    pub reaction_ticks: u8,
+    /// Pixels per tick, the sticks move a paddle 2
+    pub max_speed: i16,
+    /// Largest miss of the predicted crossing point, in pixels
+    pub prediction_error: i16,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn params(self) -> CpuParams {
+        match self {
+            Difficulty::Easy => CpuParams { reaction_ticks: 12, max_speed: 1, prediction_error: 10 },
+            Difficulty::Normal => CpuParams { reaction_ticks: 6, max_speed: 2, prediction_error: 5 },
+            Difficulty::Hard => CpuParams { reaction_ticks: 2, max_speed: 3, prediction_error: 1 },
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+}
+
+pub struct CpuPaddle {
+    pub params: CpuParams,
+    /// Whether the ball was last seen heading for this paddle
+    incoming: bool,
+    /// Ticks left before reacting to the last change of `incoming`
+    reaction: u8,
+    /// Where the paddle centre is headed
+    target: Option<i16>,
+    rng: Rand32,
+}
+
+impl CpuPaddle {
+    pub fn new(params: CpuParams, seed: u64) -> Self {
+        CpuPaddle { params, incoming: false, reaction: 0, target: None, rng: Rand32::new(seed) }
+    }
+
+    /// Starts over on another ball, with the same delay as when the ball turns
+    pub fn follow_new_ball(&mut self) {
+        self.reaction = self.params.reaction_ticks;
+        self.target = None;
+    }
+
+    /// Paddle movement for this tick. `paddle_x` is where the ball touches
+    /// the paddle, `paddle` the current centre of the paddle.
+    pub fn step(&mut self, ball: &Ball, paddle_x: i16, paddle: i16, height: i16) -> i16 {
+        let incoming = (ball.dx > 0) == (paddle_x as i32 * ONE > ball.x);
+        if incoming != self.incoming {
+            self.incoming = incoming;
+            self.reaction = self.params.reaction_ticks;
+            self.target = None;
+        }
+
+        if self.reaction > 0 {
+            self.reaction -= 1;
+        } else if self.target.is_none() {
+            let target = if incoming {
+                let error = self.params.prediction_error as i32;
+                let miss = self.rng.rand_range(0..(2 * error + 1) as u32) as i32 - error;
+                predict_y(ball, paddle_x, height) + BALL_SIZE / 2 + miss as i16
+            } else {
+                height / 2
+            };
+            self.target = Some(target);
+        }
+
+        match self.target {
+            Some(target) => (target - paddle).clamp(-self.params.max_speed, self.params.max_speed),
+            None => 0,
+        }
+    }
+}
+
+/// Height in pixels of the ball's top when it reaches column `x`, bouncing
+/// off the top and bottom walls on the way
+pub fn predict_y(ball: &Ball, x: i16, height: i16) -> i16 {
+    if ball.dx == 0 {
+        return ball.position().y;
+    }
+    let ticks = ((x as i32 * ONE - ball.x) / ball.dx).max(0);
+
+    // The ball turns at 0 and where it touches the bottom, fold the straight
+    // path back in
+    let span = (height - BALL_SIZE) as i32 * ONE;
+    let y = (ball.y + ball.dy * ticks).rem_euclid(2 * span);
+    ((if y > span { 2 * span - y } else { y }) >> FRACTION_BITS) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pong::Pong;
+
+    #[test]
+    fn prediction_follows_wall_bounces() {
+        let ball = Ball::at(80, 100).with_velocity(ONE, ONE);
+        assert_eq!(predict_y(&ball, 90, 128), 110);
+        // 79 steps down from 100 hits the bottom wall at 126 and comes back
+        assert_eq!(predict_y(&ball, 159, 128), 73);
+        assert_eq!(predict_y(&ball.with_velocity(-2 * ONE, -ONE), 0, 128), 60);
+
+        // Checked against the engine itself
+        let mut pong = Pong::new(160, 128, 3);
+        pong.balls[0] = Ball::at(100, 30).with_velocity(ONE + ONE / 2, -ONE);
+        let expected = predict_y(&pong.balls[0], 148, 128);
+        while pong.balls[0].position().x < 148 {
+            pong.update_balls();
+        }
+        assert_eq!(pong.balls[0].position().x, 148);
+        assert_eq!(pong.balls[0].position().y, expected);
+    }
+
+    #[test]
+    fn paddle_waits_then_moves_at_its_speed() {
+        let params = CpuParams { reaction_ticks: 3, max_speed: 2, prediction_error: 0 };
+        let mut cpu = CpuPaddle::new(params, 1);
+        let incoming = Ball::at(80, 64).with_velocity(ONE, ONE);
+
+        // Ball heading for the right paddle at x = 156, its top will get there
+        // at 64 + 76 folded back to 112, so its centre at 113
+        for _ in 0..3 {
+            assert_eq!(cpu.step(&incoming, 156, 20, 128), 0);
+        }
+        assert_eq!(cpu.step(&incoming, 156, 20, 128), 2);
+        assert_eq!(cpu.step(&incoming, 156, 112, 128), 1);
+
+        // Ball going away, back to the middle after the delay
+        let outgoing = incoming.with_velocity(-ONE, ONE);
+        for _ in 0..3 {
+            cpu.step(&outgoing, 156, 100, 128);
+        }
+        assert_eq!(cpu.step(&outgoing, 156, 100, 128), -2);
+    }
+
+    #[test]
+    fn harder_reacts_sooner() {
+        // Ticks until the paddle first moves and until it is as close to the
+        // crossing point as its aim allows, starting far from it
+        let meet = |difficulty: Difficulty| {
+            let mut cpu = CpuPaddle::new(difficulty.params(), 1);
+            let incoming = Ball::at(80, 64).with_velocity(ONE, ONE);
+            let crossing = predict_y(&incoming, 156, 128) + BALL_SIZE / 2;
+            let (mut paddle, mut moved) = (20, None);
+            for tick in 1..200 {
+                let movement = cpu.step(&incoming, 156, paddle, 128);
+                if movement != 0 && moved.is_none() {
+                    moved = Some(tick);
+                }
+                paddle += movement;
+                if (paddle - crossing).abs() <= cpu.params.prediction_error {
+                    return (moved.unwrap(), tick);
+                }
+            }
+            panic!("{:?} never got to the ball", difficulty);
+        };
+
+        let (easy_moved, easy_met) = meet(Difficulty::Easy);
+        let (hard_moved, hard_met) = meet(Difficulty::Hard);
+        assert!(hard_moved < easy_moved);
+        assert!(hard_met < easy_met);
+    }
+}
@@ -0,0 +1,88 @@
+//! Power-ups for arcade Pong.
+//!
+//! One at a time shows up on the centre line. A ball that runs into it hands
+//! it to the player who sent that ball, the one it is moving away from.
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::RgbColor;
+use oorandom::Rand32;
+
+use crate::pong::{Ball, BALL_SIZE};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerUpKind {
+    /// The collector's paddle grows for a while
+    LongPaddle,
+    /// The ball that took it speeds up
+    FastBall,
+    /// Another ball is served towards the opponent
+    ExtraBall,
+    /// The opponent's paddle shrinks for a while
+    ShrinkOpponent,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 4] =
+        [PowerUpKind::LongPaddle, PowerUpKind::FastBall, PowerUpKind::ExtraBall, PowerUpKind::ShrinkOpponent];
+
+    pub fn color(self) -> Rgb565 {
+        match self {
+            PowerUpKind::LongPaddle => Rgb565::GREEN,
+            PowerUpKind::FastBall => Rgb565::YELLOW,
+            PowerUpKind::ExtraBall => Rgb565::CYAN,
+            PowerUpKind::ShrinkOpponent => Rgb565::MAGENTA,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    /// Top left corner in pixels
+    pub x: i16,
+    pub y: i16,
+}
+
+impl PowerUp {
+    /// Power-ups are squares this many pixels wide
+    pub const SIZE: i16 = 4;
+
+    /// A random kind somewhere on the centre line, clear of the walls
+    pub fn random(rng: &mut Rand32, width: i16, height: i16) -> Self {
+        let kind = PowerUpKind::ALL[rng.rand_range(0..PowerUpKind::ALL.len() as u32) as usize];
+        let y = rng.rand_range(Self::SIZE as u32..(height - 2 * Self::SIZE) as u32) as i16;
+        PowerUp { kind, x: width / 2 - Self::SIZE / 2, y }
+    }
+
+    pub fn touches(&self, ball: &Ball) -> bool {
+        let ball = ball.position();
+        ball.x < self.x + Self::SIZE
+            && self.x < ball.x + BALL_SIZE
+            && ball.y < self.y + Self::SIZE
+            && self.y < ball.y + BALL_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_ups_land_on_the_centre_line() {
+        let mut rng = Rand32::new(3);
+        for _ in 0..100 {
+            let power_up = PowerUp::random(&mut rng, 160, 128);
+            assert_eq!(power_up.x, 78);
+            assert!(power_up.y >= PowerUp::SIZE && power_up.y + PowerUp::SIZE <= 128 - PowerUp::SIZE);
+        }
+    }
+
+    #[test]
+    fn touching_needs_overlap() {
+        let power_up = PowerUp { kind: PowerUpKind::ExtraBall, x: 78, y: 40 };
+        assert!(power_up.touches(&Ball::at(77, 39)));
+        assert!(power_up.touches(&Ball::at(81, 43)));
+        assert!(!power_up.touches(&Ball::at(76, 40)));
+        assert!(!power_up.touches(&Ball::at(80, 44)));
+    }
+}
@@ -0,0 +1,434 @@
+//! Pong for four players.
+//!
+//! Besides the left and right paddles two more guard the top and bottom
+//! walls. The vertical paddles follow the Y axes of the sticks and the
+//! horizontal ones the X axes, so the first stick plays left and top and the
+//! second one right and bottom: four players, or two with two paddles each.
+//!
+//! Every side starts with `LIVES` lives and loses one for each ball that gets
+//! past its paddle. A side without lives is out and a wall closes its side of
+//! the court; the last side standing wins.
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::{DrawTarget, Drawable, Point as ScreenPoint, Primitive, RgbColor, Size};
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::Text;
+use oorandom::Rand32;
+
+use crate::input::{InputSnapshot, JoyToPin, JOY_LOWER_BOUND, JOY_UPPER_BOUND};
+use crate::pong::{
+    Ball, BALL_SIZE, EDGE_ANGLE_QUARTERS, MAX_CONTACTS_PER_TICK, MAX_SPEED, ONE, PLAYER_MOVE_DELTA, PLAYER_SIZE,
+    RETURNS_PER_SPEEDUP, SERVE_SPEED, SPEEDUP,
+};
+use crate::render::Render;
+
+pub const LIVES: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left = 0,
+    Right = 1,
+    Top = 2,
+    Bottom = 3,
+}
+
+impl Side {
+    pub const ALL: [Side; 4] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Left => "Left",
+            Side::Right => "Right",
+            Side::Top => "Top",
+            Side::Bottom => "Bottom",
+        }
+    }
+
+    /// Stick axis that moves this side's paddle
+    pub fn axis(self) -> JoyToPin {
+        match self {
+            Side::Left => JoyToPin::JoyY1,
+            Side::Right => JoyToPin::JoyY2,
+            Side::Top => JoyToPin::JoyX1,
+            Side::Bottom => JoyToPin::JoyX2,
+        }
+    }
+
+    /// Left and right paddles move up and down
+    fn is_vertical(self) -> bool {
+        matches!(self, Side::Left | Side::Right)
+    }
+}
+
+pub struct FourPong {
+    pub width: i16,
+    pub height: i16,
+    pub ball: Ball,
+    /// Centre of each paddle along its wall, indexed by `Side`
+    pub paddles: [i16; 4],
+    /// Indexed by `Side`, a side with none left is out
+    pub lives: [u8; 4],
+    pub is_running: bool,
+    pub rng: Rand32,
+}
+
+impl FourPong {
+    pub fn new(width: i16, height: i16, seed: u64) -> Self {
+        let mut pong = FourPong {
+            width,
+            height,
+            ball: Ball::at(width / 2, height / 2),
+            paddles: [height / 2, height / 2, width / 2, width / 2],
+            lives: [LIVES; 4],
+            is_running: true,
+            rng: Rand32::new(seed),
+        };
+        pong.serve();
+        pong
+    }
+
+    pub fn is_out(&self, side: Side) -> bool {
+        self.lives[side as usize] == 0
+    }
+
+    /// The last side standing once the game is over
+    pub fn winner(&self) -> Option<Side> {
+        let mut alive = Side::ALL.into_iter().filter(|&side| !self.is_out(side));
+        match (alive.next(), alive.next()) {
+            (Some(side), None) => Some(side),
+            _ => None,
+        }
+    }
+
+    fn wall_length(&self, side: Side) -> i16 {
+        if side.is_vertical() { self.height } else { self.width }
+    }
+
+    pub fn move_paddle(&mut self, side: Side, delta: i16) {
+        let length = self.wall_length(side);
+        let paddle = &mut self.paddles[side as usize];
+        *paddle = (*paddle + delta).clamp(PLAYER_SIZE, length - PLAYER_SIZE);
+    }
+
+    /// Every paddle still in the game follows its own axis, see `Side::axis`
+    pub fn handle_input(&mut self, input: &InputSnapshot) {
+        for side in Side::ALL {
+            if self.is_out(side) {
+                continue;
+            }
+            let value = input.axis(side.axis());
+            let delta = if value > JOY_UPPER_BOUND {
+                PLAYER_MOVE_DELTA
+            } else if value < JOY_LOWER_BOUND {
+                -PLAYER_MOVE_DELTA
+            } else {
+                0
+            };
+            self.move_paddle(side, delta);
+        }
+    }
+
+    /// Ball back in the middle at serve speed, towards a random side that is
+    /// still in the game at a random angle
+    pub fn serve(&mut self) {
+        let mut target = Side::ALL[self.rng.rand_range(0..4) as usize];
+        while self.is_out(target) {
+            target = Side::ALL[(target as usize + 1) % 4];
+        }
+
+        let across = self.rng.rand_range(0..SERVE_SPEED as u32 + 1) as i32 - SERVE_SPEED / 2;
+        let (dx, dy) = match target {
+            Side::Left => (-SERVE_SPEED, across),
+            Side::Right => (SERVE_SPEED, across),
+            Side::Top => (across, -SERVE_SPEED),
+            Side::Bottom => (across, SERVE_SPEED),
+        };
+        let ball = Ball::at(self.width / 2, self.height / 2).with_velocity(dx, dy);
+        self.ball = Ball { speed: SERVE_SPEED, ..ball };
+    }
+
+    /// `x` (left and right) or `y` (top and bottom) of the ball when it
+    /// touches the face of a paddle, or the wall of a side that is out
+    fn contact(&self, side: Side) -> i32 {
+        match side {
+            Side::Left | Side::Top => ONE,
+            Side::Right => (self.width - 2 - BALL_SIZE) as i32 * ONE,
+            Side::Bottom => (self.height - 2 - BALL_SIZE) as i32 * ONE,
+        }
+    }
+
+    /// The first side the ball reaches within `remaining`
+    fn next_contact(&self, remaining: i32) -> Option<(i32, Side)> {
+        let ball = &self.ball;
+        let time_to = |position: i32, speed: i32, low: Side, high: Side| {
+            if speed < 0 && position > self.contact(low) {
+                Some(((position - self.contact(low)) * ONE / -speed, low))
+            } else if speed > 0 && position < self.contact(high) {
+                Some(((self.contact(high) - position) * ONE / speed, high))
+            } else {
+                None
+            }
+        };
+
+        let mut next: Option<(i32, Side)> = None;
+        let candidates = [time_to(ball.x, ball.dx, Side::Left, Side::Right), time_to(ball.y, ball.dy, Side::Top, Side::Bottom)];
+        for (time, side) in candidates.into_iter().flatten() {
+            if time <= remaining && next.map_or(true, |(first, _)| time < first) {
+                next = Some((time, side));
+            }
+        }
+        next
+    }
+
+    /// Moves the ball through one tick, from one contact to the next like
+    /// `Pong::update_balls`. A ball that gets past a paddle costs that side a
+    /// life.
+    pub fn update_ball(&mut self) {
+        let mut remaining = ONE;
+        for _ in 0..MAX_CONTACTS_PER_TICK {
+            let (time, side) = match self.next_contact(remaining) {
+                Some(next) => next,
+                None => break,
+            };
+            self.ball.advance(time);
+            remaining -= time;
+
+            if !self.bounce(side) {
+                self.lose_life(side);
+                return;
+            }
+        }
+        self.ball.advance(remaining);
+
+        // Only a ball stuck in a corner runs out of contacts, keep it in
+        let (left, right) = (self.contact(Side::Left), self.contact(Side::Right));
+        let (top, bottom) = (self.contact(Side::Top), self.contact(Side::Bottom));
+        self.ball.x = self.ball.x.clamp(left, right);
+        self.ball.y = self.ball.y.clamp(top, bottom);
+    }
+
+    /// Sends the ball back off the wall of a side that is out or off its
+    /// paddle, angled by where it hit like in `Pong`. False on a miss.
+    fn bounce(&mut self, side: Side) -> bool {
+        let contact = self.contact(side);
+        let out = self.is_out(side);
+        let paddle = self.paddles[side as usize] as i32 * ONE;
+        let ball = &mut self.ball;
+
+        // Position along the wall and speed towards it
+        let (along, towards) = if side.is_vertical() {
+            ball.x = contact;
+            (ball.y, &mut ball.dx)
+        } else {
+            ball.y = contact;
+            (ball.x, &mut ball.dy)
+        };
+
+        if out {
+            *towards = -*towards;
+            return true;
+        }
+
+        let offset = along + (BALL_SIZE / 2) as i32 * ONE - paddle;
+        if offset.abs() >= (PLAYER_SIZE + BALL_SIZE / 2) as i32 * ONE {
+            return false;
+        }
+
+        ball.returns += 1;
+        if ball.returns % RETURNS_PER_SPEEDUP == 0 {
+            ball.speed = (ball.speed + SPEEDUP).min(MAX_SPEED);
+        }
+
+        let reach = PLAYER_SIZE as i32 * ONE;
+        let away = match side {
+            Side::Left | Side::Top => ball.speed,
+            Side::Right | Side::Bottom => -ball.speed,
+        };
+        let across = offset.clamp(-reach, reach) * ball.speed * EDGE_ANGLE_QUARTERS / (4 * reach);
+        if side.is_vertical() {
+            ball.dx = away;
+            ball.dy = across;
+        } else {
+            ball.dx = across;
+            ball.dy = away;
+        }
+        true
+    }
+
+    fn lose_life(&mut self, side: Side) {
+        self.lives[side as usize] -= 1;
+        if self.winner().is_some() {
+            self.is_running = false;
+        } else {
+            self.serve();
+        }
+    }
+
+    /// One game tick: paddles, then the ball
+    pub fn update(&mut self, input: &InputSnapshot) {
+        if !self.is_running {
+            return;
+        }
+        self.handle_input(input);
+        self.update_ball();
+    }
+}
+
+impl Render for FourPong {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let paddle_style = PrimitiveStyle::with_fill(Rgb565::WHITE);
+        let wall_style = PrimitiveStyle::with_fill(Rgb565::BLUE);
+        let text_style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        let (width, height) = (self.width as i32, self.height as i32);
+        let length = (PLAYER_SIZE * 2) as u32;
+
+        for side in Side::ALL {
+            let start = (self.paddles[side as usize] - PLAYER_SIZE) as i32;
+            // Paddle or wall, then the lives next to it
+            let (paddle, wall, lives_at) = match side {
+                Side::Left => ((0, start, 1, length), (0, 0, 1, height as u32), (6, height / 2 + 4)),
+                Side::Right => ((width - 2, start, 1, length), (width - 2, 0, 1, height as u32), (width - 12, height / 2 + 4)),
+                Side::Top => ((start, 0, length, 1), (0, 0, width as u32, 1), (width / 2 - 3, 12)),
+                Side::Bottom => ((start, height - 2, length, 1), (0, height - 2, width as u32, 1), (width / 2 - 3, height - 6)),
+            };
+
+            if self.is_out(side) {
+                let (x, y, w, h) = wall;
+                Rectangle::new(ScreenPoint::new(x, y), Size::new(w, h)).into_styled(wall_style).draw(target)?;
+            } else {
+                let (x, y, w, h) = paddle;
+                Rectangle::new(ScreenPoint::new(x, y), Size::new(w, h)).into_styled(paddle_style).draw(target)?;
+                let mut buf = itoa::Buffer::new();
+                let (x, y) = lives_at;
+                Text::new(buf.format(self.lives[side as usize]), ScreenPoint::new(x, y), text_style).draw(target)?;
+            }
+        }
+
+        let ball = self.ball.position();
+        Rectangle::new(ScreenPoint::new(ball.x as i32, ball.y as i32), Size::new(BALL_SIZE as u32, BALL_SIZE as u32))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::RED))
+            .draw(target)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+    use crate::input::JOY_MAX_VAL;
+
+    #[test]
+    fn paddles_follow_both_axes() {
+        let mut pong = FourPong::new(160, 128, 1);
+        let input = InputSnapshot::CENTERED
+            .with_axis(JoyToPin::JoyX1, JOY_MAX_VAL)
+            .with_axis(JoyToPin::JoyY2, 0);
+        pong.handle_input(&input);
+
+        assert_eq!(pong.paddles, [64, 64 - PLAYER_MOVE_DELTA, 80 + PLAYER_MOVE_DELTA, 80]);
+    }
+
+    #[test]
+    fn returns_angle_off_every_paddle() {
+        let mut pong = FourPong::new(160, 128, 1);
+
+        // Straight up into the middle of the top paddle and straight back
+        pong.ball = Ball::at(79, 3).with_velocity(0, -ONE);
+        pong.ball.speed = ONE;
+        pong.update_ball();
+        pong.update_ball();
+        assert_eq!((pong.ball.dx, pong.ball.dy), (0, ONE));
+
+        // The right end of the bottom paddle sends it off to the right
+        pong.ball = Ball::at(83, 123).with_velocity(0, ONE);
+        pong.ball.speed = ONE;
+        pong.update_ball();
+        assert_eq!((pong.ball.dx, pong.ball.dy), (3 * ONE / 4, -ONE));
+        assert_eq!(pong.lives, [LIVES; 4]);
+    }
+
+    #[test]
+    fn a_miss_costs_a_life_and_the_last_one_closes_the_side() {
+        let mut pong = FourPong::new(160, 128, 1);
+        pong.paddles[Side::Top as usize] = 20;
+
+        pong.ball = Ball::at(100, 3).with_velocity(0, -ONE);
+        pong.update_ball();
+        pong.update_ball();
+        assert_eq!(pong.lives, [LIVES, LIVES, LIVES - 1, LIVES]);
+        assert_eq!(pong.ball.position().y, 64);
+
+        // Out of lives, the ball bounces off the top wall wherever it goes
+        pong.lives[Side::Top as usize] = 1;
+        pong.ball = Ball::at(100, 2).with_velocity(0, -ONE);
+        pong.update_ball();
+        assert!(pong.is_out(Side::Top));
+        assert!(pong.is_running);
+
+        pong.ball = Ball::at(100, 2).with_velocity(ONE, -ONE);
+        pong.update_ball();
+        assert_eq!((pong.ball.dx, pong.ball.dy), (ONE, ONE));
+    }
+
+    #[test]
+    fn last_side_standing_wins() {
+        let mut pong = FourPong::new(160, 128, 3);
+        pong.lives = [1, 0, 2, 0];
+        assert_eq!(pong.winner(), None);
+
+        pong.paddles[Side::Left as usize] = 100;
+        pong.ball = Ball::at(3, 30).with_velocity(-ONE, 0);
+        for _ in 0..3 {
+            pong.update_ball();
+        }
+        assert!(!pong.is_running);
+        assert_eq!(pong.winner(), Some(Side::Top));
+    }
+
+    #[test]
+    fn serves_only_go_to_sides_in_the_game() {
+        let mut pong = FourPong::new(160, 128, 5);
+        pong.lives = [0, 2, 0, 1];
+        for _ in 0..20 {
+            pong.serve();
+            assert!(pong.ball.dx > 0 || pong.ball.dy > 0);
+            assert_eq!(pong.ball.speed, SERVE_SPEED);
+        }
+    }
+
+    #[test]
+    fn ball_stays_in_the_court() {
+        let mut pong = FourPong::new(160, 128, 9);
+        for _ in 0..5_000 {
+            pong.update(&InputSnapshot::CENTERED);
+            let ball = pong.ball.position();
+            assert!((0..=160 - BALL_SIZE).contains(&ball.x), "ball at x = {}", ball.x);
+            assert!((0..=128 - BALL_SIZE).contains(&ball.y), "ball at y = {}", ball.y);
+        }
+        assert!(!pong.is_running);
+    }
+
+    #[test]
+    fn render_draws_paddles_and_closed_walls() {
+        let mut pong = FourPong::new(160, 128, 1);
+        pong.lives[Side::Right as usize] = 0;
+        pong.paddles[Side::Bottom as usize] = 30;
+
+        let mut framebuffer: Framebuffer = Framebuffer::new();
+        pong.render(&mut framebuffer).unwrap();
+
+        assert_eq!(framebuffer.pixel(80, 0), Some(Rgb565::WHITE));
+        assert_eq!(framebuffer.pixel(30, 126), Some(Rgb565::WHITE));
+        assert_eq!(framebuffer.pixel(80, 126), Some(Rgb565::BLACK));
+        for y in [0, 64, 127] {
+            assert_eq!(framebuffer.pixel(158, y), Some(Rgb565::BLUE));
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+
+/// Something that can draw itself on the panel. `render` draws the whole
+/// scene on top of a black background every frame, `DirtyDisplay` takes care
+/// of sending only what changed.
+pub trait Render {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>;
+}
+
+impl<T: Render + ?Sized> Render for &T {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        (**self).render(target)
+    }
+}
+
+/// Draws the first scene, then the second one on top, e.g. a debug overlay
+impl<A: Render, B: Render> Render for (A, B) {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        self.0.render(target)?;
+        self.1.render(target)
+    }
+}
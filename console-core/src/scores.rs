@@ -0,0 +1,230 @@
+//! Top scores per game with the players' initials and the furthest Snake
+//! campaign level, and their encoding in the save record (see `storage`).
+
+use crate::storage::PAYLOAD_SIZE;
+
+pub const TOP_N: usize = 5;
+/// Tables reserved in the save record, more than there are games so adding
+/// one does not invalidate existing saves. Growing it only appends tables,
+/// older records have zeros there, which read back as empty slots.
+pub const MAX_GAMES: usize = 8;
+pub const INITIALS_LEN: usize = 3;
+
+const ENTRY_SIZE: usize = INITIALS_LEN + 2;
+/// Bytes used by `HighScores::encode`
+pub const ENCODED_SIZE: usize = MAX_GAMES * TOP_N * ENTRY_SIZE;
+/// Payload byte after the tables holding the campaign level, older records
+/// have a zero there, the first level
+const CAMPAIGN_LEVEL_OFFSET: usize = ENCODED_SIZE;
+const _: () = assert!(CAMPAIGN_LEVEL_OFFSET < PAYLOAD_SIZE);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreEntry {
+    /// Uppercase ASCII letters
+    pub initials: [u8; INITIALS_LEN],
+    pub score: u16,
+}
+
+impl ScoreEntry {
+    pub fn initials_str(&self) -> &str {
+        core::str::from_utf8(&self.initials).unwrap_or("???")
+    }
+}
+
+pub type ScoreTable = [Option<ScoreEntry>; TOP_N];
+
+pub struct HighScores {
+    tables: [ScoreTable; MAX_GAMES],
+    campaign_level: u8,
+    unsaved: bool,
+}
+
+impl HighScores {
+    pub const fn new() -> Self {
+        HighScores { tables: [[None; TOP_N]; MAX_GAMES], campaign_level: 0, unsaved: false }
+    }
+
+    /// Best first, empty slots at the end
+    pub fn table(&self, game: usize) -> &ScoreTable {
+        &self.tables[game]
+    }
+
+    pub fn tables(&self) -> &[ScoreTable; MAX_GAMES] {
+        &self.tables
+    }
+
+    /// Zero never makes it onto the table
+    pub fn qualifies(&self, game: usize, score: u16) -> bool {
+        match self.tables[game][TOP_N - 1] {
+            Some(last) => score > last.score,
+            None => score > 0,
+        }
+    }
+
+    /// Inserts the entry below any equal score and returns its rank (0 is the
+    /// best), `None` when it did not qualify
+    pub fn insert(&mut self, game: usize, entry: ScoreEntry) -> Option<usize> {
+        if !self.qualifies(game, entry.score) {
+            return None;
+        }
+
+        let table = &mut self.tables[game];
+        let rank = table
+            .iter()
+            .position(|slot| slot.map_or(true, |existing| entry.score > existing.score))?;
+        table[rank..].rotate_right(1);
+        table[rank] = Some(entry);
+        self.unsaved = true;
+        Some(rank)
+    }
+
+    /// Furthest Snake campaign level reached, the campaign resumes there
+    pub fn campaign_level(&self) -> u8 {
+        self.campaign_level
+    }
+
+    /// Notes that the campaign got to level `index`, only going further counts
+    pub fn reach_campaign_level(&mut self, index: u8) {
+        if index > self.campaign_level {
+            self.campaign_level = index;
+            self.unsaved = true;
+        }
+    }
+
+    /// True once after every change that has not been written to flash yet
+    pub fn take_unsaved(&mut self) -> bool {
+        core::mem::replace(&mut self.unsaved, false)
+    }
+
+    pub fn encode(&self, out: &mut [u8; ENCODED_SIZE]) {
+        let slots = self.tables.iter().flatten();
+        for (bytes, slot) in out.chunks_exact_mut(ENTRY_SIZE).zip(slots) {
+            match slot {
+                Some(entry) => {
+                    bytes[..INITIALS_LEN].copy_from_slice(&entry.initials);
+                    bytes[INITIALS_LEN..].copy_from_slice(&entry.score.to_le_bytes());
+                }
+                None => bytes.fill(0),
+            }
+        }
+    }
+
+    /// The save record payload; the tables sit at its start, followed by the
+    /// campaign level
+    pub fn to_payload(&self) -> [u8; PAYLOAD_SIZE] {
+        let mut payload = [0; PAYLOAD_SIZE];
+        self.encode((&mut payload[..ENCODED_SIZE]).try_into().unwrap());
+        payload[CAMPAIGN_LEVEL_OFFSET] = self.campaign_level;
+        payload
+    }
+
+    pub fn from_payload(payload: &[u8; PAYLOAD_SIZE]) -> Self {
+        let mut scores = Self::decode(payload[..ENCODED_SIZE].try_into().unwrap());
+        scores.campaign_level = payload[CAMPAIGN_LEVEL_OFFSET];
+        scores
+    }
+
+    /// Anything that does not look like an entry is dropped, so stale or
+    /// foreign data ends up as empty slots
+    pub fn decode(bytes: &[u8; ENCODED_SIZE]) -> Self {
+        let mut scores = HighScores::new();
+        let slots = scores.tables.iter_mut().flatten();
+        for (bytes, slot) in bytes.chunks_exact(ENTRY_SIZE).zip(slots) {
+            let initials = [bytes[0], bytes[1], bytes[2]];
+            let score = u16::from_le_bytes([bytes[INITIALS_LEN], bytes[INITIALS_LEN + 1]]);
+            if score > 0 && initials.iter().all(u8::is_ascii_uppercase) {
+                *slot = Some(ScoreEntry { initials, score });
+            }
+        }
+        scores
+    }
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(initials: &[u8; 3], score: u16) -> ScoreEntry {
+        ScoreEntry { initials: *initials, score }
+    }
+
+    #[test]
+    fn table_keeps_best_scores_in_order() {
+        let mut scores = HighScores::new();
+        assert!(!scores.qualifies(1, 0));
+
+        assert_eq!(scores.insert(1, entry(b"AAA", 10)), Some(0));
+        assert_eq!(scores.insert(1, entry(b"BBB", 30)), Some(0));
+        assert_eq!(scores.insert(1, entry(b"CCC", 10)), Some(2));
+        assert_eq!(scores.insert(1, entry(b"DDD", 5)), Some(3));
+        assert_eq!(scores.insert(1, entry(b"EEE", 20)), Some(1));
+
+        // Full now, the lowest entry drops off
+        assert!(!scores.qualifies(1, 5));
+        assert_eq!(scores.insert(1, entry(b"FFF", 6)), Some(4));
+
+        let initials: [&[u8; 3]; TOP_N] = [b"BBB", b"EEE", b"AAA", b"CCC", b"FFF"];
+        for (slot, expected) in scores.table(1).iter().zip(initials) {
+            assert_eq!(&slot.unwrap().initials, expected);
+        }
+        assert!(scores.table(0).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn only_changes_need_saving() {
+        let mut scores = HighScores::new();
+        assert!(!scores.take_unsaved());
+
+        scores.insert(0, entry(b"ABC", 7));
+        assert!(scores.take_unsaved());
+        assert!(!scores.take_unsaved());
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let mut scores = HighScores::new();
+        scores.insert(0, entry(b"PNG", 9));
+        scores.insert(1, entry(b"SNK", 300));
+        scores.insert(1, entry(b"XYZ", 4));
+
+        let mut bytes = [0xFF; ENCODED_SIZE];
+        scores.encode(&mut bytes);
+        let decoded = HighScores::decode(&bytes);
+
+        for game in 0..MAX_GAMES {
+            assert_eq!(decoded.table(game), scores.table(game));
+        }
+        assert_eq!(HighScores::decode(&[0xFF; ENCODED_SIZE]).table(1), &[None; TOP_N]);
+    }
+
+    #[test]
+    fn records_from_fewer_tables_still_load() {
+        // A record written with 4 tables, the rest of the payload zeroed
+        let mut scores = HighScores::new();
+        scores.insert(3, entry(b"OLD", 12));
+        let mut payload = scores.to_payload();
+        payload[4 * TOP_N * ENTRY_SIZE..].fill(0);
+
+        let loaded = HighScores::from_payload(&payload);
+        assert_eq!(loaded.table(3)[0], Some(entry(b"OLD", 12)));
+        assert!(loaded.tables()[4..].iter().flatten().all(Option::is_none));
+    }
+
+    #[test]
+    fn campaign_level_is_saved_and_only_grows() {
+        let mut scores = HighScores::new();
+        scores.reach_campaign_level(0);
+        assert!(!scores.take_unsaved());
+
+        scores.reach_campaign_level(3);
+        scores.reach_campaign_level(1);
+        assert!(scores.take_unsaved());
+        assert_eq!(HighScores::from_payload(&scores.to_payload()).campaign_level(), 3);
+    }
+}
@@ -0,0 +1,701 @@
+//! Screens outside the games themselves: the game over screen, the high
+//! score table, the initials entry shown after a qualifying game, the pause
+//! menu and the settings.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, PrimitiveStyleBuilder, Rectangle};
+use embedded_graphics::text::Text;
+use heapless::String;
+
+use crate::input::{AxisEdge, ButtonEdge, InputSnapshot, JoyToPin};
+use crate::render::Render;
+use crate::scores::{HighScores, ScoreEntry, ScoreTable, INITIALS_LEN, MAX_GAMES};
+use crate::games::{GameResult, GAMES};
+use crate::pong::{PongConfig, StickMode};
+use crate::pong_ai::Difficulty;
+use crate::settings::Settings;
+use crate::snake::{Arena, SnakeDifficulty};
+
+/// Top scores of one game at a time, the first stick flips between games.
+/// Demos keep no scores and are skipped.
+pub struct HighScoreScreen {
+    pub game: usize,
+    /// Rank to highlight, the entry that was just added
+    pub highlight: Option<usize>,
+    /// Copy of the tables, they can not change while the screen is open
+    tables: [ScoreTable; MAX_GAMES],
+    stick: AxisEdge,
+    button: ButtonEdge,
+}
+
+impl HighScoreScreen {
+    pub fn new(scores: &HighScores, game: usize, highlight: Option<usize>) -> Self {
+        HighScoreScreen {
+            game,
+            highlight,
+            tables: *scores.tables(),
+            stick: AxisEdge::default(),
+            button: ButtonEdge::held(),
+        }
+    }
+
+    /// Returns true once the first button asks to go back to the menu
+    pub fn update(&mut self, input: &InputSnapshot) -> bool {
+        let step = self.stick.step(input.axis(JoyToPin::JoyX1));
+        if step != 0 {
+            let count = GAMES.len() as isize;
+            loop {
+                self.game = (self.game as isize + step as isize).rem_euclid(count) as usize;
+                if !GAMES[self.game].demo {
+                    break;
+                }
+            }
+            self.highlight = None;
+        }
+
+        self.button.pressed(input.button1)
+    }
+}
+
+impl Render for HighScoreScreen {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        let highlight_style = MonoTextStyle::new(&FONT_6X10, Rgb565::YELLOW);
+
+        Text::new("High Scores", Point::new(46, 20), style).draw(target)?;
+        let name = GAMES[self.game].name;
+        Text::new("<", Point::new(20, 38), style).draw(target)?;
+        Text::new(name, Point::new(80 - 3 * name.len() as i32, 38), style).draw(target)?;
+        Text::new(">", Point::new(134, 38), style).draw(target)?;
+
+        for (rank, slot) in self.tables[self.game].iter().enumerate() {
+            let y = 58 + 12 * rank as i32;
+            let style = if self.highlight == Some(rank) { highlight_style } else { style };
+
+            let mut buf = itoa::Buffer::new();
+            Text::new(buf.format(rank + 1), Point::new(40, y), style).draw(target)?;
+            match slot {
+                Some(entry) => {
+                    Text::new(entry.initials_str(), Point::new(58, y), style).draw(target)?;
+                    Text::new(buf.format(entry.score), Point::new(88, y), style).draw(target)?;
+                }
+                None => {
+                    Text::new("---", Point::new(58, y), style).draw(target)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameOverChoice {
+    /// On to the initials entry for a new high score, the menu otherwise
+    Continue,
+    /// Same game with a new seed
+    PlayAgain,
+}
+
+const GAME_OVER_CHOICES: [(GameOverChoice, &str); 2] =
+    [(GameOverChoice::Continue, "Continue"), (GameOverChoice::PlayAgain, "Play again")];
+
+/// Result of the game that just ended, shown until a button is pressed
+pub struct GameOver {
+    /// Index in `GAMES`
+    pub id: usize,
+    pub seed: u64,
+    pub result: GameResult,
+    pub played_us: u64,
+    /// The result makes the high score table, initials are asked next
+    pub new_high_score: bool,
+    pub selected: usize,
+    stick: AxisEdge,
+    button: ButtonEdge,
+}
+
+impl GameOver {
+    pub fn new(id: usize, seed: u64, result: GameResult, played_us: u64, new_high_score: bool) -> Self {
+        GameOver {
+            id,
+            seed,
+            result,
+            played_us,
+            new_high_score,
+            selected: 0,
+            stick: AxisEdge::default(),
+            button: ButtonEdge::held(),
+        }
+    }
+
+    pub fn update(&mut self, input: &InputSnapshot) -> Option<GameOverChoice> {
+        match self.stick.step(input.axis(JoyToPin::JoyY1)) {
+            1 => self.selected = (self.selected + 1).min(GAME_OVER_CHOICES.len() - 1),
+            -1 => self.selected = self.selected.saturating_sub(1),
+            _ => {}
+        }
+
+        if self.button.pressed(input.button1) {
+            Some(GAME_OVER_CHOICES[self.selected].0)
+        } else {
+            None
+        }
+    }
+
+    /// Seed for "Play again", mixed from the last one and the stick noise
+    pub fn next_seed(&self, input: &InputSnapshot) -> u64 {
+        self.seed.wrapping_mul(31).wrapping_add(input.axis(JoyToPin::JoyX1) as u64)
+    }
+}
+
+impl Render for GameOver {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        let title_style = MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE);
+        let mut line: String<24> = String::new();
+
+        match self.result {
+            GameResult::Pong { player1_score, player2_score, player1_sets, player2_sets, against_cpu } => {
+                let player1_won = player1_sets > player2_sets;
+                if against_cpu {
+                    let title = if player1_won { "You win!" } else { "CPU wins" };
+                    Text::new(title, Point::new(40, 28), title_style).draw(target)?;
+                } else {
+                    let _ = write!(line, "Player {} wins", if player1_won { 1 } else { 2 });
+                    Text::new(&line, Point::new(10, 28), title_style).draw(target)?;
+                }
+
+                line.clear();
+                if player1_sets + player2_sets > 1 {
+                    let _ = write!(line, "Sets {} - {}  ({} - {})", player1_sets, player2_sets, player1_score, player2_score);
+                    Text::new(&line, Point::new(17, 48), style).draw(target)?;
+                } else {
+                    let _ = write!(line, "{} - {}", player1_score, player2_score);
+                    Text::new(&line, Point::new(62, 48), style).draw(target)?;
+                }
+            }
+            GameResult::Snake { won, length, score, level } => {
+                let title = if won { "You win!" } else { "Game Over" };
+                Text::new(title, Point::new(35, 28), title_style).draw(target)?;
+
+                // The campaign shows how far it got instead of the length
+                match level {
+                    Some(level) => {
+                        let _ = write!(line, "Level {}  Score {}", level + 1, score);
+                    }
+                    None => {
+                        let _ = write!(line, "Length {}  Score {}", length, score);
+                    }
+                }
+                Text::new(&line, Point::new(20, 48), style).draw(target)?;
+            }
+            GameResult::FourPong { winner, lives } => {
+                let _ = write!(line, "{} wins", winner.name());
+                Text::new(&line, Point::new(80 - 5 * line.len() as i32, 28), title_style).draw(target)?;
+
+                line.clear();
+                let _ = write!(line, "Lives left {}", lives);
+                Text::new(&line, Point::new(44, 48), style).draw(target)?;
+            }
+        }
+
+        let seconds = self.played_us / 1_000_000;
+        line.clear();
+        let _ = write!(line, "Time {}:{:02}", seconds / 60, seconds % 60);
+        Text::new(&line, Point::new(50, 62), style).draw(target)?;
+
+        if self.new_high_score {
+            let highlight = MonoTextStyle::new(&FONT_6X10, Rgb565::YELLOW);
+            Text::new("New high score!", Point::new(35, 76), highlight).draw(target)?;
+        }
+
+        for (i, (_, label)) in GAME_OVER_CHOICES.iter().enumerate() {
+            let y = 96 + 12 * i as i32;
+            let cursor = if i == self.selected { ">" } else { " " };
+            Text::new(cursor, Point::new(40, y), style).draw(target)?;
+            Text::new(label, Point::new(52, y), style).draw(target)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Three letters picked with the first stick: up and down change the letter,
+/// left and right move between them, the first button saves
+pub struct InitialsEntry {
+    pub game: usize,
+    pub score: u16,
+    /// Seed to start the game again with once the entry is saved, instead of
+    /// showing the table
+    pub play_again: Option<u64>,
+    pub initials: [u8; INITIALS_LEN],
+    pub cursor: usize,
+    letter_stick: AxisEdge,
+    cursor_stick: AxisEdge,
+    button: ButtonEdge,
+}
+
+impl InitialsEntry {
+    pub fn new(game: usize, score: u16, play_again: Option<u64>) -> Self {
+        InitialsEntry {
+            game,
+            score,
+            play_again,
+            initials: [b'A'; INITIALS_LEN],
+            cursor: 0,
+            letter_stick: AxisEdge::default(),
+            cursor_stick: AxisEdge::default(),
+            button: ButtonEdge::held(),
+        }
+    }
+
+    /// Returns the finished entry once the first button is pressed
+    pub fn update(&mut self, input: &InputSnapshot) -> Option<ScoreEntry> {
+        let letter = &mut self.initials[self.cursor];
+        match self.letter_stick.step(input.axis(JoyToPin::JoyY1)) {
+            1 => *letter = if *letter == b'Z' { b'A' } else { *letter + 1 },
+            -1 => *letter = if *letter == b'A' { b'Z' } else { *letter - 1 },
+            _ => {}
+        }
+
+        match self.cursor_stick.step(input.axis(JoyToPin::JoyX1)) {
+            1 => self.cursor = (self.cursor + 1).min(INITIALS_LEN - 1),
+            -1 => self.cursor = self.cursor.saturating_sub(1),
+            _ => {}
+        }
+
+        if self.button.pressed(input.button1) {
+            Some(ScoreEntry { initials: self.initials, score: self.score })
+        } else {
+            None
+        }
+    }
+}
+
+impl Render for InitialsEntry {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        Text::new("New High Score!", Point::new(34, 20), style).draw(target)?;
+        Text::new(GAMES[self.game].name, Point::new(20, 38), style).draw(target)?;
+        let mut buf = itoa::Buffer::new();
+        Text::new(buf.format(self.score), Point::new(120, 38), style).draw(target)?;
+
+        let letter_style = MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE);
+        for (i, &letter) in self.initials.iter().enumerate() {
+            let x = 58 + 16 * i as i32;
+            let mut text = [0; 4];
+            let letter = char::from(letter).encode_utf8(&mut text);
+            Text::new(letter, Point::new(x, 80), letter_style).draw(target)?;
+
+            if i == self.cursor {
+                Rectangle::new(Point::new(x, 84), Size::new(10, 2))
+                    .into_styled(PrimitiveStyle::with_fill(Rgb565::YELLOW))
+                    .draw(target)?;
+            }
+        }
+
+        Text::new("Button 1 saves", Point::new(38, 110), style).draw(target)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PauseChoice {
+    Resume,
+    /// Same game again with the same seed
+    Restart,
+    Quit,
+}
+
+const PAUSE_CHOICES: [(PauseChoice, &str); 3] =
+    [(PauseChoice::Resume, "Resume"), (PauseChoice::Restart, "Restart"), (PauseChoice::Quit, "Quit")];
+
+/// Overlay drawn over the frozen game. The first stick picks an entry and
+/// button 1 takes it; button 2, which opened the menu, also resumes.
+pub struct PauseMenu {
+    pub selected: usize,
+    stick: AxisEdge,
+    confirm_button: ButtonEdge,
+    resume_button: ButtonEdge,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        PauseMenu {
+            selected: 0,
+            stick: AxisEdge::default(),
+            confirm_button: ButtonEdge::held(),
+            resume_button: ButtonEdge::held(),
+        }
+    }
+
+    pub fn update(&mut self, input: &InputSnapshot) -> Option<PauseChoice> {
+        match self.stick.step(input.axis(JoyToPin::JoyY1)) {
+            1 => self.selected = (self.selected + 1).min(PAUSE_CHOICES.len() - 1),
+            -1 => self.selected = self.selected.saturating_sub(1),
+            _ => {}
+        }
+
+        if self.resume_button.pressed(input.button2) {
+            Some(PauseChoice::Resume)
+        } else if self.confirm_button.pressed(input.button1) {
+            Some(PAUSE_CHOICES[self.selected].0)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for PauseMenu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Render for PauseMenu {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let frame_style = PrimitiveStyleBuilder::new()
+            .fill_color(Rgb565::BLACK)
+            .stroke_color(Rgb565::WHITE)
+            .stroke_width(1)
+            .build();
+        Rectangle::new(Point::new(40, 30), Size::new(80, 68)).into_styled(frame_style).draw(target)?;
+
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        Text::new("Paused", Point::new(62, 45), style).draw(target)?;
+
+        for (i, (_, label)) in PAUSE_CHOICES.iter().enumerate() {
+            let y = 63 + 12 * i as i32;
+            let cursor = if i == self.selected { ">" } else { " " };
+            Text::new(cursor, Point::new(50, y), style).draw(target)?;
+            Text::new(label, Point::new(62, y), style).draw(target)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Setting {
+    CpuLevel,
+    /// Stick mode of the player with this index
+    Paddle(usize),
+    Points,
+    WinByTwo,
+    Sets,
+    Serve,
+    Arcade,
+    SnakeArena,
+    SnakeSpeed,
+    SnakeSpeedUp,
+}
+
+const SETTINGS_ROWS: [(Setting, &str); 11] = [
+    (Setting::CpuLevel, "CPU level"),
+    (Setting::Paddle(0), "P1 paddle"),
+    (Setting::Paddle(1), "P2 paddle"),
+    (Setting::Points, "Points"),
+    (Setting::WinByTwo, "Win by 2"),
+    (Setting::Sets, "Best of"),
+    (Setting::Serve, "Serve"),
+    (Setting::Arcade, "Arcade"),
+    (Setting::SnakeArena, "Snake arena"),
+    (Setting::SnakeSpeed, "Snake speed"),
+    (Setting::SnakeSpeedUp, "Speed up"),
+];
+/// Rows that fit between the title and the footer, the list scrolls
+const VISIBLE_SETTINGS: usize = 7;
+
+/// Options for the games. Up and down on the first stick pick a row, left and
+/// right change its value. Button 1 goes back to the menu and keeps the
+/// changes.
+pub struct SettingsScreen {
+    pub settings: Settings,
+    pub selected: usize,
+    /// First row shown, the list scrolls to keep `selected` on screen
+    pub first_visible: usize,
+    row_stick: AxisEdge,
+    value_stick: AxisEdge,
+    button: ButtonEdge,
+}
+
+impl SettingsScreen {
+    pub fn new(settings: Settings) -> Self {
+        SettingsScreen {
+            settings,
+            selected: 0,
+            first_visible: 0,
+            row_stick: AxisEdge::default(),
+            value_stick: AxisEdge::default(),
+            button: ButtonEdge::held(),
+        }
+    }
+
+    /// Returns true once the first button asks to go back
+    pub fn update(&mut self, input: &InputSnapshot) -> bool {
+        match self.row_stick.step(input.axis(JoyToPin::JoyY1)) {
+            1 => self.selected = (self.selected + 1).min(SETTINGS_ROWS.len() - 1),
+            -1 => self.selected = self.selected.saturating_sub(1),
+            _ => {}
+        }
+        if self.selected < self.first_visible {
+            self.first_visible = self.selected;
+        } else if self.selected >= self.first_visible + VISIBLE_SETTINGS {
+            self.first_visible = self.selected + 1 - VISIBLE_SETTINGS;
+        }
+
+        let step = self.value_stick.step(input.axis(JoyToPin::JoyX1));
+        if step != 0 {
+            let settings = &mut self.settings;
+            let pong = &mut settings.pong;
+            match SETTINGS_ROWS[self.selected].0 {
+                Setting::CpuLevel => settings.cpu_difficulty = cycle(&Difficulty::ALL, settings.cpu_difficulty, step),
+                Setting::Paddle(i) => settings.stick_modes[i] = cycle(&StickMode::ALL, settings.stick_modes[i], step),
+                Setting::Points => pong.target_score = cycle(&PongConfig::TARGET_SCORES, pong.target_score, step),
+                Setting::WinByTwo => pong.win_by_two = !pong.win_by_two,
+                Setting::Sets => pong.sets = cycle(&PongConfig::SET_COUNTS, pong.sets, step),
+                Setting::Serve => pong.alternate_serve = !pong.alternate_serve,
+                Setting::Arcade => pong.arcade = !pong.arcade,
+                Setting::SnakeArena => settings.snake_arena = cycle(&Arena::ALL, settings.snake_arena, step),
+                Setting::SnakeSpeed => {
+                    settings.snake_difficulty = cycle(&SnakeDifficulty::ALL, settings.snake_difficulty, step)
+                }
+                Setting::SnakeSpeedUp => settings.snake_speed_up = !settings.snake_speed_up,
+            }
+        }
+
+        self.button.pressed(input.button1)
+    }
+
+    fn write_value(&self, setting: Setting, out: &mut String<12>) {
+        let pong = &self.settings.pong;
+        let on_off = |on| if on { "On" } else { "Off" };
+        let _ = match setting {
+            Setting::CpuLevel => out.push_str(self.settings.cpu_difficulty.name()),
+            Setting::Paddle(i) => out.push_str(self.settings.stick_modes[i].name()),
+            Setting::Points => write!(out, "{}", pong.target_score).map_err(drop),
+            Setting::WinByTwo => out.push_str(on_off(pong.win_by_two)),
+            Setting::Sets if pong.sets == 1 => out.push_str("1 set"),
+            Setting::Sets => write!(out, "{} sets", pong.sets).map_err(drop),
+            Setting::Serve => out.push_str(if pong.alternate_serve { "Turns" } else { "Random" }),
+            Setting::Arcade => out.push_str(on_off(pong.arcade)),
+            Setting::SnakeArena => out.push_str(self.settings.snake_arena.name()),
+            Setting::SnakeSpeed => out.push_str(self.settings.snake_difficulty.name()),
+            Setting::SnakeSpeedUp => out.push_str(on_off(self.settings.snake_speed_up)),
+        };
+    }
+}
+
+/// Next (`step` 1) or previous (`step` -1) entry of `options` after `value`,
+/// wrapping around
+fn cycle<T: Copy + PartialEq>(options: &[T], value: T, step: i8) -> T {
+    let count = options.len() as isize;
+    let index = options.iter().position(|&option| option == value).unwrap_or(0) as isize;
+    options[(index + step as isize).rem_euclid(count) as usize]
+}
+
+impl Render for SettingsScreen {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        Text::new("Settings", Point::new(56, 14), style).draw(target)?;
+
+        let last_visible = SETTINGS_ROWS.len().min(self.first_visible + VISIBLE_SETTINGS);
+        for (row, (setting, label)) in SETTINGS_ROWS[self.first_visible..last_visible].iter().enumerate() {
+            let y = 34 + 12 * row as i32;
+            let cursor = if self.first_visible + row == self.selected { ">" } else { " " };
+            Text::new(cursor, Point::new(4, y), style).draw(target)?;
+            Text::new(label, Point::new(16, y), style).draw(target)?;
+
+            let mut value = String::new();
+            self.write_value(*setting, &mut value);
+            Text::new("<", Point::new(84, y), style).draw(target)?;
+            Text::new(&value, Point::new(96, y), style).draw(target)?;
+            Text::new(">", Point::new(148, y), style).draw(target)?;
+        }
+
+        // More rows above or below the visible ones
+        if self.first_visible > 0 {
+            Text::new("^", Point::new(154, 34), style).draw(target)?;
+        }
+        if last_visible < SETTINGS_ROWS.len() {
+            Text::new("v", Point::new(154, 34 + 12 * (VISIBLE_SETTINGS as i32 - 1)), style).draw(target)?;
+        }
+
+        Text::new("Button 1 returns", Point::new(32, 122), style).draw(target)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputSource, ScriptedInput, JOY_MAX_VAL};
+
+    #[test]
+    fn initials_follow_stick() {
+        let frames = [
+            // Still holding the button that ended the game
+            InputSnapshot::CENTERED.with_buttons(true, false),
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, 0),
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL),
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL),
+            InputSnapshot::CENTERED,
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL),
+            InputSnapshot::CENTERED.with_buttons(true, false),
+        ];
+        let mut input = ScriptedInput::new(&frames);
+        let mut entry = InitialsEntry::new(1, 42, None);
+
+        let mut result = None;
+        while !input.is_finished() {
+            assert!(result.is_none());
+            result = entry.update(&input.read());
+        }
+
+        let result = result.unwrap();
+        assert_eq!(result.initials_str(), "ZCA");
+        assert_eq!(result.score, 42);
+    }
+
+    #[test]
+    fn table_screen_cycles_games() {
+        let mut screen = HighScoreScreen::new(&HighScores::new(), 0, Some(2));
+
+        assert!(!screen.update(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, 0)));
+        assert_eq!(Some(screen.game), GAMES.iter().rposition(|entry| !entry.demo));
+        assert_eq!(screen.highlight, None);
+
+        assert!(!screen.update(&InputSnapshot::CENTERED));
+        assert!(screen.update(&InputSnapshot::CENTERED.with_buttons(true, false)));
+    }
+
+    #[test]
+    fn pause_menu_choices() {
+        let mut pause = PauseMenu::new();
+        // Button 2 is still down from opening the menu
+        assert_eq!(pause.update(&InputSnapshot::CENTERED.with_buttons(false, true)), None);
+        assert_eq!(pause.update(&InputSnapshot::CENTERED.with_buttons(false, true)), None);
+        assert_eq!(pause.update(&InputSnapshot::CENTERED), None);
+
+        let down = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        pause.update(&down);
+        assert_eq!(pause.update(&InputSnapshot::CENTERED.with_buttons(true, false)), Some(PauseChoice::Restart));
+
+        pause.update(&InputSnapshot::CENTERED);
+        assert_eq!(pause.update(&InputSnapshot::CENTERED.with_buttons(false, true)), Some(PauseChoice::Resume));
+    }
+
+    #[test]
+    fn game_over_offers_continue_and_play_again() {
+        let result = GameResult::Snake { won: false, length: 9, score: 6, level: None };
+        let mut over = GameOver::new(1, 5, result, 83_000_000, false);
+
+        // The button that was down when the game ended is ignored
+        assert_eq!(over.update(&InputSnapshot::CENTERED.with_buttons(true, false)), None);
+        assert_eq!(over.update(&InputSnapshot::CENTERED.with_buttons(true, false)), None);
+        assert_eq!(over.update(&InputSnapshot::CENTERED), None);
+
+        over.update(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL));
+        assert_eq!(over.update(&InputSnapshot::CENTERED.with_buttons(true, false)), Some(GameOverChoice::PlayAgain));
+        assert_ne!(over.next_seed(&InputSnapshot::CENTERED), over.seed);
+    }
+
+    #[test]
+    fn settings_change_cpu_level_and_paddles() {
+        let mut screen = SettingsScreen::new(Settings::default());
+        assert!(!screen.update(&InputSnapshot::CENTERED.with_buttons(true, false)));
+
+        screen.update(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, 0));
+        assert_eq!(screen.settings.cpu_difficulty, Difficulty::Easy);
+
+        // Second player's paddle, two rows down
+        let down = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        for frame in [down, InputSnapshot::CENTERED, down, InputSnapshot::CENTERED] {
+            screen.update(&frame);
+        }
+        screen.update(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL));
+        assert_eq!(screen.settings.stick_modes, [StickMode::Digital, StickMode::Proportional]);
+        assert!(screen.update(&InputSnapshot::CENTERED.with_buttons(true, false)));
+    }
+
+    #[test]
+    fn settings_pick_match_rules() {
+        let mut screen = SettingsScreen::new(Settings::default());
+        let down = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        let right = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL);
+        let left = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, 0);
+
+        for frame in [down, InputSnapshot::CENTERED].iter().cycle().take(6) {
+            screen.update(frame);
+        }
+        screen.update(&right);
+        screen.update(&InputSnapshot::CENTERED);
+        screen.update(&right);
+        screen.update(&down);
+        screen.update(&left);
+        screen.update(&InputSnapshot::CENTERED);
+        screen.update(&down);
+        screen.update(&left);
+
+        let pong = screen.settings.pong;
+        assert_eq!(pong.target_score, 21);
+        assert!(pong.win_by_two);
+        assert_eq!(pong.sets, 5);
+
+        let mut value = String::new();
+        screen.write_value(Setting::Sets, &mut value);
+        assert_eq!(value.as_str(), "5 sets");
+    }
+
+    #[test]
+    fn options_cycle_both_ways() {
+        assert_eq!(cycle(&Difficulty::ALL, Difficulty::Normal, 1), Difficulty::Hard);
+        assert_eq!(cycle(&Difficulty::ALL, Difficulty::Hard, 1), Difficulty::Easy);
+        assert_eq!(cycle(&Difficulty::ALL, Difficulty::Easy, -1), Difficulty::Hard);
+    }
+
+    #[test]
+    fn settings_scroll_to_the_snake_options() {
+        let mut screen = SettingsScreen::new(Settings::default());
+        let down = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        let up = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, 0);
+        let right = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL);
+
+        for frame in [down, InputSnapshot::CENTERED].iter().cycle().take(2 * SETTINGS_ROWS.len()) {
+            screen.update(frame);
+        }
+        assert_eq!(screen.selected, SETTINGS_ROWS.len() - 1);
+        assert_eq!(screen.first_visible, SETTINGS_ROWS.len() - VISIBLE_SETTINGS);
+
+        // Speed up, Snake speed and Snake arena from the bottom
+        for _ in 0..3 {
+            screen.update(&right);
+            screen.update(&up);
+            screen.update(&InputSnapshot::CENTERED);
+        }
+        assert!(screen.settings.snake_speed_up);
+        assert_eq!(screen.settings.snake_difficulty, SnakeDifficulty::Hard);
+        assert_eq!(screen.settings.snake_arena, Arena::Wrap);
+        assert_eq!(screen.first_visible, SETTINGS_ROWS.len() - VISIBLE_SETTINGS);
+    }
+}
@@ -0,0 +1,20 @@
+//! Options picked on the settings screen. They apply to every game started
+//! afterwards and last until power off.
+
+use crate::pong::{PongConfig, StickMode};
+use crate::pong_ai::Difficulty;
+use crate::snake::{Arena, SnakeDifficulty};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Settings {
+    /// How well the computer plays Pong
+    pub cpu_difficulty: Difficulty,
+    /// How each player's stick moves their Pong paddle, indexed by `PlayerTurn`
+    pub stick_modes: [StickMode; 2],
+    /// Match rules for every Pong mode
+    pub pong: PongConfig,
+    pub snake_arena: Arena,
+    pub snake_difficulty: SnakeDifficulty,
+    /// Snake gets faster as the score grows
+    pub snake_speed_up: bool,
+}
@@ -0,0 +1,843 @@
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::{DrawTarget, Drawable, Point as ScreenPoint, Primitive, RgbColor, Size};
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::Text;
+use heapless::{Deque, Vec};
+use oorandom::Rand32;
+use crate::input::{InputSnapshot, JoyToPin, JOY_LOWER_BOUND, JOY_UPPER_BOUND};
+use crate::render::Render;
+use crate::snake_body::{Body, Occupancy};
+use crate::snake_level::{campaign_level, Level, Wall};
+
+pub const SNAKE_INITIAL_LENGTH: usize = 3;
+/// Side of a grid cell in pixels on the console's screen
+pub const CELL_SIZE: i16 = 4;
+/// Width of the line drawn around the playfield
+const BORDER: i16 = 1;
+/// Room left above the playfield for the score in `Arena::Inset`
+const HUD_HEIGHT: i16 = 12;
+/// Most food items the field can hold at once
+pub const MAX_FOOD: usize = 8;
+/// Food items on the field unless `Snake::with_food_count` says otherwise
+pub const DEFAULT_FOOD_COUNT: usize = 1;
+/// Random cells tried for food before walking the grid for a free one
+const FOOD_TRIES: usize = 8;
+/// The snake moves one cell every 50 ms on `SnakeDifficulty::Normal`
+pub const TICK_PERIOD_US: u32 = 50_000;
+/// No speed makes the snake move faster than this
+pub const MIN_TICK_PERIOD_US: u32 = 15_000;
+/// With speeding up on, the tick period halves after this much food
+const SPEEDUP_FOOD: u32 = 32;
+/// Turns that can wait for the coming moves
+const TURN_QUEUE_LEN: usize = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// Turn requested by a stick deflection, horizontal axis first
+    pub fn from_joy(x_val: u16, y_val: u16) -> Option<Direction> {
+        if x_val > JOY_UPPER_BOUND {
+            Some(Direction::Right)
+        } else if x_val < JOY_LOWER_BOUND {
+            Some(Direction::Left)
+        } else if y_val > JOY_UPPER_BOUND {
+            Some(Direction::Up)
+        } else if y_val < JOY_LOWER_BOUND {
+            Some(Direction::Down)
+        } else {
+            None
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    /// Cell offset of one move, `Up` counts rows downwards
+    pub fn delta(self) -> (i16, i16) {
+        match self {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+}
+
+/// How fast the snake moves, picked before the game
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SnakeDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Insane,
+}
+
+impl SnakeDifficulty {
+    pub const ALL: [SnakeDifficulty; 4] =
+        [SnakeDifficulty::Easy, SnakeDifficulty::Normal, SnakeDifficulty::Hard, SnakeDifficulty::Insane];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SnakeDifficulty::Easy => "Easy",
+            SnakeDifficulty::Normal => "Normal",
+            SnakeDifficulty::Hard => "Hard",
+            SnakeDifficulty::Insane => "Insane",
+        }
+    }
+
+    /// Time between moves at the start of a game
+    pub fn tick_period_us(self) -> u32 {
+        match self {
+            SnakeDifficulty::Easy => 80_000,
+            SnakeDifficulty::Normal => TICK_PERIOD_US,
+            SnakeDifficulty::Hard => 35_000,
+            SnakeDifficulty::Insane => 20_000,
+        }
+    }
+}
+
+/// What the edges of the playfield do, picked when the game starts
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Arena {
+    /// The edges of the screen are walls
+    #[default]
+    Walls,
+    /// Leaving at one edge comes back in at the opposite one
+    Wrap,
+    /// Walls inset from the top of the screen, the score sits above them
+    Inset,
+}
+
+impl Arena {
+    pub const ALL: [Arena; 3] = [Arena::Walls, Arena::Wrap, Arena::Inset];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Arena::Walls => "Walls",
+            Arena::Wrap => "Wrap",
+            Arena::Inset => "Inset",
+        }
+    }
+}
+
+#[derive(PartialEq,Clone, Copy, Debug)]
+pub struct Point {
+    pub x: i16,
+    pub y: i16,
+}
+
+/// Progress through the levels in `snake_level::LEVELS`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Campaign {
+    /// Index of the level being played
+    pub index: u8,
+    pub level: Level<'static>,
+    /// Food eaten on this level
+    pub eaten: u8,
+}
+
+/// The snake lives on a grid: positions, `width` and `height` are in cells,
+/// and only rendering scales them to pixels
+#[derive(Debug)]
+pub struct Snake {
+    pub width: i16,
+    pub height: i16,
+    /// Side of a cell in pixels
+    pub cell_size: i16,
+    /// Pixel of the top left corner of the playfield
+    pub origin: Point,
+    pub arena: Arena,
+    pub head_position: Point,
+    /// Head first, see `snake_body`
+    pub body: Body,
+    pub direction: Direction,
+    /// Turns for the coming moves, one is taken per move
+    pub turns: Deque<Direction, TURN_QUEUE_LEN>,
+    pub score: u16,
+    pub alive: bool,
+    pub ate: bool,
+    pub food: Vec<Point,MAX_FOOD>,
+    /// Food items kept on the field, one is spawned for each one eaten
+    pub food_count: usize,
+    pub won: bool,
+    /// Wall cells inside the playfield, see `snake_level`
+    pub obstacles: Occupancy,
+    obstacle_cells: usize,
+    /// `None` outside the campaign
+    pub campaign: Option<Campaign>,
+    /// Time between moves on `SnakeDifficulty::Normal` before speeding up,
+    /// levels set their own, see `tick_period_us`
+    pub base_tick_period_us: u32,
+    pub difficulty: SnakeDifficulty,
+    /// Whether the snake gets faster as the score grows
+    pub speed_up: bool,
+    pub rng: Rand32
+}
+
+
+impl Snake {
+    /// The largest grid of `cell_size` cells that fits a `width` x `height`
+    /// pixel screen inside the border, centred on it below the HUD if the
+    /// arena has one
+    pub fn for_screen(width: i16, height: i16, cell_size: i16, arena: Arena, seed: u64) -> Self {
+        let top = if arena == Arena::Inset { HUD_HEIGHT } else { 0 };
+        let columns = (width - 2 * BORDER) / cell_size;
+        let rows = (height - top - 2 * BORDER) / cell_size;
+        let origin = Point { x: (width - columns * cell_size) / 2, y: top + (height - top - rows * cell_size) / 2 };
+        Self::new(columns, rows, cell_size, arena, seed).at(origin)
+    }
+
+    /// A `width` x `height` cell grid with its top left corner just inside
+    /// the border
+    pub fn new(width: i16, height: i16, cell_size: i16, arena: Arena, seed: u64) -> Self {
+        let mut body = Body::new(width, height);
+
+        let mid_x = width/2;
+        let mid_y = height/2;
+
+        body.push_head(Point { x:mid_x + 2, y: mid_y });
+        body.push_head(Point { x:mid_x + 1, y: mid_y });
+        body.push_head(Point { x:mid_x, y: mid_y });
+
+
+        let mut snake = Snake {
+            width,
+            height,
+            cell_size,
+            origin: Point { x: BORDER, y: BORDER },
+            arena,
+            head_position: Point { x: width / 2, y: height / 2 },
+            body,
+            direction: Direction::Left,
+            turns: Deque::new(),
+            score: SNAKE_INITIAL_LENGTH as u16,
+            alive: true,
+            ate: false,
+            food: Vec::new(),
+            food_count: DEFAULT_FOOD_COUNT,
+            won: false,
+            obstacles: Occupancy::new(width, height),
+            obstacle_cells: 0,
+            campaign: None,
+            base_tick_period_us: TICK_PERIOD_US,
+            difficulty: SnakeDifficulty::Normal,
+            speed_up: false,
+            rng: Rand32::new(seed)
+        };
+        snake.refill_food();
+        snake
+    }
+
+    /// Campaign on the inset arena of a `width` x `height` pixel screen,
+    /// starting at level `index` or the last one if there are fewer
+    pub fn campaign(width: i16, height: i16, cell_size: i16, index: u8, seed: u64) -> Self {
+        let mut snake = Self::for_screen(width, height, cell_size, Arena::Inset, seed);
+        let last = (0..=index).rev().find(|&index| campaign_level(index).is_some()).unwrap_or(0);
+        snake.load_level(last);
+        snake
+    }
+
+    /// Puts up the walls of campaign level `index` and starts the snake over
+    /// at its start; the score carries on
+    pub fn load_level(&mut self, index: u8) {
+        let Some(level) = campaign_level(index) else {
+            return;
+        };
+
+        self.obstacles.clear();
+        self.obstacle_cells = 0;
+        for cell in level.walls().flat_map(Wall::cells) {
+            if !self.obstacles.get(cell) {
+                self.obstacles.set(cell, true);
+                self.obstacle_cells += 1;
+            }
+        }
+
+        self.body.clear();
+        let start: Vec<Point, SNAKE_INITIAL_LENGTH> = level.start_cells().collect();
+        for &cell in start.iter().rev() {
+            self.body.push_head(cell);
+        }
+        self.head_position = level.start;
+        self.direction = level.direction;
+        self.turns.clear();
+        self.ate = false;
+        self.base_tick_period_us = level.tick_period_us;
+        self.campaign = Some(Campaign { index, level, eaten: 0 });
+
+        self.food.clear();
+        self.refill_food();
+    }
+
+    /// Moves at the pace of `difficulty`, getting faster with the score when
+    /// `speed_up` is set
+    pub fn with_speed(mut self, difficulty: SnakeDifficulty, speed_up: bool) -> Self {
+        self.difficulty = difficulty;
+        self.speed_up = speed_up;
+        self
+    }
+
+    /// How often `update` should run right now: the base period scaled by the
+    /// difficulty and, when speeding up, shrinking with the food eaten
+    pub fn tick_period_us(&self) -> u32 {
+        let base = self.base_tick_period_us as u64 * self.difficulty.tick_period_us() as u64 / TICK_PERIOD_US as u64;
+        let period = if self.speed_up {
+            let eaten = self.score.saturating_sub(SNAKE_INITIAL_LENGTH as u16) as u64;
+            base * SPEEDUP_FOOD as u64 / (SPEEDUP_FOOD as u64 + eaten)
+        } else {
+            base
+        };
+        (period as u32).max(MIN_TICK_PERIOD_US)
+    }
+
+    /// Keeps `count` food items on the field, at most `MAX_FOOD`
+    pub fn with_food_count(mut self, count: usize) -> Self {
+        self.food_count = count.min(MAX_FOOD);
+        self.food.clear();
+        self.refill_food();
+        self
+    }
+
+    /// Moves the playfield to pixel `origin` on the screen
+    pub fn at(mut self, origin: Point) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Pixel rectangle covered by a cell
+    fn cell_rectangle(&self, cell: Point) -> Rectangle {
+        let size = self.cell_size as u32;
+        let x = self.origin.x + cell.x * self.cell_size;
+        let y = self.origin.y + cell.y * self.cell_size;
+        Rectangle::new(ScreenPoint::new(x as i32, y as i32), Size::new(size, size))
+    }
+
+    /// Queues a turn for a coming move. It is checked against the direction
+    /// the snake will have by then, so turning back or keeping that direction
+    /// is dropped, as is anything past a full queue.
+    pub fn change_direction(&mut self, new_direction: Direction) {
+        let heading = self.turns.back().copied().unwrap_or(self.direction);
+        if new_direction != heading && new_direction != heading.opposite() {
+            let _ = self.turns.push_back(new_direction);
+        }
+    }
+
+    pub fn handle_input(&mut self, input: &InputSnapshot) {
+        if let Some(direction) = Direction::from_joy(input.axis(JoyToPin::JoyX1), input.axis(JoyToPin::JoyY1)) {
+            self.change_direction(direction);
+        }
+    }
+
+    pub fn move_snake(&mut self) {
+        if let Some(turn) = self.turns.pop_front() {
+            if turn != self.direction.opposite() {
+                self.direction = turn;
+            }
+        }
+
+        let (dx, dy) = self.direction.delta();
+        let mut new_head = Point { x: self.head_position.x + dx, y: self.head_position.y + dy };
+
+        if self.arena == Arena::Wrap {
+            new_head.x = new_head.x.rem_euclid(self.width);
+            new_head.y = new_head.y.rem_euclid(self.height);
+        }
+
+        if new_head.x < 0 || new_head.x >= self.width || new_head.y < 0 || new_head.y >= self.height {
+            self.alive = false;
+            return;
+        }
+
+        if self.body.contains(new_head) || self.obstacles.get(new_head) {
+            self.alive = false;
+            return;
+        }
+
+        self.body.push_head(new_head);
+        if !self.ate {
+            self.body.pop_tail();
+        } else {
+            self.ate = false;
+        }
+        self.head_position = new_head;
+
+        if self.body.len() == self.width as usize * self.height as usize - self.obstacle_cells {
+            self.won = true;
+        }
+    }
+
+    pub fn eat(&mut self) {
+        self.score += 1;
+        self.ate = true;
+
+        if let Some(i) = self.food.iter().position(|food| *food == self.head_position) {
+            self.food.swap_remove(i);
+        }
+    }
+
+    fn is_free(&self, cell: Point) -> bool {
+        !self.body.contains(cell) && !self.obstacles.get(cell) && !self.food.contains(&cell)
+    }
+
+    /// Puts a food item on a random free cell, false when there is none.
+    /// Every free cell is equally likely. A few random cells are tried
+    /// first; on a crowded board it falls back to one pass over the grid,
+    /// so it always ends.
+    pub fn spawn_food(&mut self) -> bool {
+        let cells = self.width as usize * self.height as usize;
+        let free = cells - self.obstacle_cells - self.body.len() - self.food.len();
+        if free == 0 || self.food.is_full() {
+            return false;
+        }
+
+        for _ in 0..FOOD_TRIES {
+            let x = self.rng.rand_range(0..self.width as u32) as i16;
+            let y = self.rng.rand_range(0..self.height as u32) as i16;
+            let cell = Point { x, y };
+            if self.is_free(cell) {
+                return self.food.push(cell).is_ok();
+            }
+        }
+
+        let pick = self.rng.rand_range(0..free as u32) as usize;
+        let cell = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| Point { x, y }))
+            .filter(|&cell| self.is_free(cell))
+            .nth(pick);
+        match cell {
+            Some(cell) => self.food.push(cell).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Tops the field up to `food_count` items, or as many as there is room
+    /// for
+    pub fn refill_food(&mut self) {
+        while self.food.len() < self.food_count && self.spawn_food() {}
+    }
+
+    /// One game tick: turn, move, then eat whatever is under the head. In
+    /// the campaign, reaching the level's food target moves on to the next
+    /// level, and finishing the last one wins.
+    pub fn update(&mut self, input: &InputSnapshot) {
+        self.handle_input(input);
+        self.move_snake();
+
+        if self.alive && self.food.contains(&self.head_position) {
+            self.eat();
+            self.refill_food();
+
+            if let Some(campaign) = &mut self.campaign {
+                campaign.eaten += 1;
+                if campaign.eaten >= campaign.level.food_target {
+                    let next = campaign.index + 1;
+                    if campaign_level(next).is_some() {
+                        self.load_level(next);
+                    } else {
+                        self.won = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Render for Snake {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let snake_style = PrimitiveStyle::with_fill(Rgb565::GREEN);
+        let food_style = PrimitiveStyle::with_fill(Rgb565::RED);
+        // A wrapping edge is no wall, it is drawn dimmer
+        let border_color = if self.arena == Arena::Wrap { Rgb565::new(6, 12, 6) } else { Rgb565::BLUE };
+        let border_style = PrimitiveStyle::with_stroke(border_color, BORDER as u32);
+
+        // Drawn just outside the cells
+        let border = Rectangle::new(
+            ScreenPoint::new((self.origin.x - BORDER) as i32, (self.origin.y - BORDER) as i32),
+            Size::new(
+                (self.width * self.cell_size + 2 * BORDER) as u32,
+                (self.height * self.cell_size + 2 * BORDER) as u32,
+            ),
+        );
+        border.into_styled(border_style).draw(target)?;
+
+        if let Some(campaign) = &self.campaign {
+            for wall in campaign.level.walls() {
+                let top_left = self.cell_rectangle(Point { x: wall.x, y: wall.y }).top_left;
+                let size = Size::new((wall.width * self.cell_size) as u32, (wall.height * self.cell_size) as u32);
+                Rectangle::new(top_left, size).into_styled(PrimitiveStyle::with_fill(Rgb565::BLUE)).draw(target)?;
+            }
+        }
+
+        for segment in self.body.iter() {
+            self.cell_rectangle(segment).into_styled(snake_style).draw(target)?;
+        }
+
+        for food in self.food.iter() {
+            self.cell_rectangle(*food).into_styled(food_style).draw(target)?;
+        }
+
+        let mut buf = itoa::Buffer::new();
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        if self.arena == Arena::Inset {
+            // In the HUD above the border
+            let hud = ScreenPoint::new(self.origin.x as i32, (self.origin.y - BORDER - 3) as i32);
+            Text::new("Score", hud, style).draw(target)?;
+            Text::new(buf.format(self.score), hud + ScreenPoint::new(36, 0), style).draw(target)?;
+            if let Some(campaign) = &self.campaign {
+                let right = ScreenPoint::new((self.origin.x + self.width * self.cell_size) as i32 - 48, hud.y);
+                Text::new("Level", right, style).draw(target)?;
+                Text::new(buf.format(campaign.index + 1), right + ScreenPoint::new(36, 0), style).draw(target)?;
+            }
+        } else {
+            Text::new(buf.format(self.score), ScreenPoint::new(70, 20), style).draw(target)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+    use crate::input::{InputSource, ScriptedInput, JOY_MAX_VAL};
+
+    #[test]
+    fn stick_turns_snake() {
+        let frames = [
+            InputSnapshot::CENTERED,
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL),
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL),
+        ];
+        let mut input = ScriptedInput::new(&frames);
+        let mut snake = Snake::new(40, 32, CELL_SIZE, Arena::Walls, 1);
+
+        snake.update(&input.read());
+        assert_eq!(snake.direction, Direction::Left);
+
+        snake.update(&input.read());
+        assert_eq!(snake.direction, Direction::Up);
+
+        snake.update(&input.read());
+        assert_eq!(snake.direction, Direction::Right);
+    }
+
+    #[test]
+    fn turns_between_moves_wait_their_turn() {
+        let up = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        let right = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL);
+        let mut snake = Snake::new(40, 32, CELL_SIZE, Arena::Walls, 1);
+        snake.food.clear();
+
+        // Up then right inside one move, the stick polled in between
+        snake.handle_input(&up);
+        snake.handle_input(&up);
+        snake.handle_input(&right);
+        assert_eq!(snake.direction, Direction::Left);
+
+        snake.update(&right);
+        assert_eq!((snake.direction, snake.head_position), (Direction::Up, Point { x: 20, y: 17 }));
+        snake.update(&right);
+        assert_eq!((snake.direction, snake.head_position), (Direction::Right, Point { x: 21, y: 17 }));
+        assert!(snake.alive && snake.turns.is_empty());
+    }
+
+    #[test]
+    fn stick_never_reverses_the_snake() {
+        let up = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        let down = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, 0);
+        let left = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, 0);
+        let right = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL);
+        let mut snake = Snake::new(40, 32, CELL_SIZE, Arena::Walls, 1);
+        snake.food.clear();
+
+        // Straight back is dropped
+        snake.handle_input(&right);
+        snake.update(&right);
+        assert_eq!((snake.direction, snake.head_position), (Direction::Left, Point { x: 19, y: 16 }));
+
+        // Up then down inside one move would fold the snake onto itself
+        snake.handle_input(&up);
+        snake.handle_input(&down);
+        snake.update(&down);
+        snake.update(&down);
+        assert_eq!((snake.direction, snake.head_position), (Direction::Up, Point { x: 19, y: 18 }));
+        assert!(snake.alive);
+
+        // Beyond the queue turns are dropped
+        snake.handle_input(&left);
+        snake.handle_input(&down);
+        snake.handle_input(&right);
+        assert!(snake.turns.iter().eq(&[Direction::Left, Direction::Down]));
+
+        // A turn queued before the direction was set elsewhere is checked again
+        snake.turns.clear();
+        snake.handle_input(&left);
+        snake.direction = Direction::Right;
+        snake.update(&InputSnapshot::CENTERED);
+        assert_eq!((snake.direction, snake.head_position), (Direction::Right, Point { x: 20, y: 18 }));
+        assert!(snake.alive);
+    }
+
+    #[test]
+    fn grid_fits_the_screen() {
+        let snake = Snake::for_screen(160, 128, 4, Arena::Walls, 1);
+        assert_eq!((snake.width, snake.height), (39, 31));
+        assert_eq!(snake.origin, Point { x: 2, y: 2 });
+
+        let snake = Snake::for_screen(160, 128, 8, Arena::Walls, 1);
+        assert_eq!((snake.width, snake.height), (19, 15));
+        assert_eq!(snake.origin, Point { x: 4, y: 4 });
+    }
+
+    #[test]
+    fn render_draws_body_and_food() {
+        let mut snake = Snake::for_screen(160, 128, 4, Arena::Walls, 1);
+        snake.food.clear();
+        snake.food.push(Point { x: 2, y: 25 }).unwrap();
+
+        let mut framebuffer: Framebuffer = Framebuffer::new();
+        snake.render(&mut framebuffer).unwrap();
+
+        // Cells 19 to 21 of row 15, 4 pixels each from the origin at 2, 2
+        for x in 78..90 {
+            for y in 62..66 {
+                assert_eq!(framebuffer.pixel(x, y), Some(Rgb565::GREEN), "body at {}, {}", x, y);
+            }
+        }
+        assert_eq!(framebuffer.pixel(90, 62), Some(Rgb565::BLACK));
+        assert_eq!(framebuffer.pixel(77, 62), Some(Rgb565::BLACK));
+        for (x, y) in [(10, 102), (13, 105)] {
+            assert_eq!(framebuffer.pixel(x, y), Some(Rgb565::RED));
+        }
+
+        // Border one pixel outside the 156 x 124 playfield
+        for (x, y) in [(1, 1), (158, 1), (1, 126), (158, 126), (80, 1), (1, 64)] {
+            assert_eq!(framebuffer.pixel(x, y), Some(Rgb565::BLUE), "border at {}, {}", x, y);
+        }
+        assert_eq!(framebuffer.pixel(2, 2), Some(Rgb565::BLACK));
+        assert_eq!(framebuffer.pixel(0, 0), Some(Rgb565::BLACK));
+    }
+
+    /// Snake on a `width` x `height` grid filling it row by row from the top
+    /// left, `length` cells long with the head last
+    fn coiled_snake(width: i16, height: i16, length: usize, seed: u64) -> Snake {
+        let mut snake = Snake::new(width, height, CELL_SIZE, Arena::Walls, seed);
+        snake.body.clear();
+        for i in 0..length as i16 {
+            snake.body.push_head(Point { x: i % width, y: i / width });
+        }
+        snake.head_position = snake.body.head().unwrap();
+        snake.food.clear();
+        snake
+    }
+
+    #[test]
+    fn food_never_spawns_on_the_body() {
+        for seed in 0..50 {
+            let snake = coiled_snake(6, 5, 22, seed).with_food_count(4);
+            assert_eq!(snake.food.len(), 4);
+            for (i, food) in snake.food.iter().enumerate() {
+                assert!(!snake.body.contains(*food), "food on the body at {:?}", food);
+                assert!(!snake.food[..i].contains(food), "two food items at {:?}", food);
+                assert!((0..6).contains(&food.x) && (0..5).contains(&food.y));
+            }
+        }
+    }
+
+    #[test]
+    fn nearly_full_board_still_spawns() {
+        // One free cell left, and it is always found
+        for seed in 0..10 {
+            let mut snake = coiled_snake(4, 4, 15, seed).with_food_count(3);
+            assert_eq!(snake.food.as_slice(), &[Point { x: 3, y: 3 }]);
+            assert!(!snake.spawn_food());
+        }
+
+        let mut snake = coiled_snake(4, 4, 16, 1);
+        snake.refill_food();
+        assert!(snake.food.is_empty());
+    }
+
+    #[test]
+    fn food_only_spawns_when_eaten() {
+        let mut snake = Snake::new(40, 32, CELL_SIZE, Arena::Walls, 3).with_food_count(2);
+        snake.food.clear();
+        snake.food.push(Point { x: 10, y: 20 }).unwrap();
+        snake.food.push(Point { x: 15, y: 16 }).unwrap();
+
+        // Head starts at 20, 16 heading left
+        for _ in 0..4 {
+            snake.update(&InputSnapshot::CENTERED);
+        }
+        assert_eq!(snake.food.as_slice(), &[Point { x: 10, y: 20 }, Point { x: 15, y: 16 }]);
+
+        snake.update(&InputSnapshot::CENTERED);
+        assert_eq!(snake.score, SNAKE_INITIAL_LENGTH as u16 + 1);
+        assert_eq!(snake.food.len(), 2);
+        assert_eq!(snake.food[0], Point { x: 10, y: 20 });
+        assert!(!snake.body.contains(snake.food[1]) && snake.food[1] != snake.head_position);
+    }
+
+    #[test]
+    fn snake_grows_past_a_hundred_and_wins_on_a_full_board() {
+        // All but the last row filled, head at the end of the row above it
+        let mut snake = coiled_snake(20, 8, 140, 1);
+        assert!(snake.body.len() > 100);
+        snake.direction = Direction::Up;
+        snake.ate = true;
+
+        // Along the last row, eating all the way
+        for x in (0..20).rev() {
+            snake.food.clear();
+            snake.food.push(Point { x, y: 7 }).unwrap();
+            snake.update(&InputSnapshot::CENTERED);
+            assert!(snake.alive, "died at {}", x);
+            snake.direction = Direction::Left;
+        }
+        assert_eq!(snake.body.len(), 160);
+        assert!(snake.won);
+    }
+
+    #[test]
+    fn walls_kill_and_wrapping_edges_do_not() {
+        for (arena, survives) in [(Arena::Walls, false), (Arena::Wrap, true), (Arena::Inset, false)] {
+            // Head at 5, 4 heading left, the food out of the way
+            let mut snake = Snake::new(10, 8, CELL_SIZE, arena, 1);
+            snake.food.clear();
+            for _ in 0..6 {
+                snake.move_snake();
+            }
+            assert_eq!(snake.alive, survives, "{:?}", arena);
+        }
+
+        let mut snake = Snake::new(10, 8, CELL_SIZE, Arena::Wrap, 1);
+        for _ in 0..6 {
+            snake.move_snake();
+        }
+        assert_eq!(snake.head_position, Point { x: 9, y: 4 });
+        snake.direction = Direction::Down;
+        for _ in 0..5 {
+            snake.move_snake();
+        }
+        assert_eq!(snake.head_position, Point { x: 9, y: 7 });
+    }
+
+    #[test]
+    fn inset_arena_leaves_room_for_the_hud() {
+        let snake = Snake::for_screen(160, 128, 4, Arena::Inset, 1);
+        assert_eq!((snake.width, snake.height), (39, 28));
+        assert_eq!(snake.origin, Point { x: 2, y: 14 });
+
+        let mut framebuffer: Framebuffer = Framebuffer::new();
+        snake.render(&mut framebuffer).unwrap();
+        assert_eq!(framebuffer.pixel(1, 13), Some(Rgb565::BLUE));
+        assert_eq!(framebuffer.pixel(1, 12), Some(Rgb565::BLACK));
+        let hud_lit = (0..160).flat_map(|x| (0..12).map(move |y| (x, y)))
+            .any(|(x, y)| framebuffer.pixel(x, y) == Some(Rgb565::WHITE));
+        assert!(hud_lit, "score missing from the HUD");
+
+        // Food stays inside the smaller field
+        let snake = Snake::for_screen(160, 128, 4, Arena::Inset, 1).with_food_count(MAX_FOOD);
+        assert!(snake.food.iter().all(|food| (0..39).contains(&food.x) && (0..28).contains(&food.y)));
+    }
+
+    #[test]
+    fn campaign_walls_kill_and_food_target_advances() {
+        let mut snake = Snake::campaign(160, 128, CELL_SIZE, 1, 4);
+        assert_eq!(snake.campaign.map(|campaign| campaign.index), Some(1));
+        assert_eq!(snake.tick_period_us(), 55_000);
+        assert!(snake.obstacles.get(Point { x: 9, y: 6 }));
+        assert!(snake.food.iter().all(|&food| !snake.obstacles.get(food)));
+
+        // Eating the target amount loads the next level with the score kept
+        let target = snake.campaign.unwrap().level.food_target;
+        for _ in 0..target {
+            let ahead = Point { x: snake.head_position.x - 1, y: snake.head_position.y };
+            snake.food[0] = ahead;
+            snake.update(&InputSnapshot::CENTERED);
+        }
+        let campaign = snake.campaign.unwrap();
+        assert_eq!((campaign.index, campaign.eaten), (2, 0));
+        assert_eq!(snake.score, SNAKE_INITIAL_LENGTH as u16 + target as u16);
+        assert_eq!(snake.body.len(), SNAKE_INITIAL_LENGTH);
+        assert_eq!(snake.head_position, campaign.level.start);
+
+        // Level 3 has a bar across row 7, steer into it
+        snake.food.clear();
+        snake.direction = Direction::Down;
+        for _ in 0..6 {
+            snake.move_snake();
+        }
+        assert!(snake.alive);
+        snake.move_snake();
+        assert!(!snake.alive);
+    }
+
+    #[test]
+    fn finishing_the_last_level_wins() {
+        let mut snake = Snake::campaign(160, 128, CELL_SIZE, 200, 4);
+        assert_eq!(snake.campaign.unwrap().index, 4);
+
+        snake.campaign.as_mut().unwrap().eaten = snake.campaign.unwrap().level.food_target - 1;
+        snake.food[0] = Point { x: snake.head_position.x + 1, y: snake.head_position.y };
+        snake.update(&InputSnapshot::CENTERED);
+        assert!(snake.won && snake.alive);
+    }
+
+    #[test]
+    fn render_draws_walls_and_level() {
+        let snake = Snake::campaign(160, 128, CELL_SIZE, 1, 4);
+        let mut framebuffer: Framebuffer = Framebuffer::new();
+        snake.render(&mut framebuffer).unwrap();
+
+        // Post at cell 9, 6
+        let x = snake.origin.x + 9 * CELL_SIZE;
+        let y = snake.origin.y + 6 * CELL_SIZE;
+        assert_eq!(framebuffer.pixel(x as i32, y as i32), Some(Rgb565::BLUE));
+        let level_lit = (112..160).flat_map(|x| (0..12).map(move |y| (x, y)))
+            .any(|(x, y)| framebuffer.pixel(x, y) == Some(Rgb565::WHITE));
+        assert!(level_lit, "level missing from the HUD");
+    }
+
+    #[test]
+    fn difficulty_sets_the_pace_and_speeding_up_shortens_it() {
+        let periods = SnakeDifficulty::ALL.map(|difficulty| {
+            Snake::new(40, 32, CELL_SIZE, Arena::Walls, 1).with_speed(difficulty, false).tick_period_us()
+        });
+        assert_eq!(periods, [80_000, 50_000, 35_000, 20_000]);
+
+        let mut snake = Snake::new(40, 32, CELL_SIZE, Arena::Walls, 1).with_speed(SnakeDifficulty::Normal, true);
+        assert_eq!(snake.tick_period_us(), TICK_PERIOD_US);
+        snake.score += SPEEDUP_FOOD as u16;
+        assert_eq!(snake.tick_period_us(), TICK_PERIOD_US / 2);
+        snake.score = u16::MAX;
+        assert_eq!(snake.tick_period_us(), MIN_TICK_PERIOD_US);
+
+        // Without speeding up the score does not matter
+        snake.speed_up = false;
+        assert_eq!(snake.tick_period_us(), TICK_PERIOD_US);
+
+        // Campaign levels keep their pace relative to the difficulty
+        let snake = Snake::campaign(160, 128, CELL_SIZE, 0, 1).with_speed(SnakeDifficulty::Easy, false);
+        assert_eq!(snake.tick_period_us(), 96_000);
+    }
+}
@@ -0,0 +1,176 @@
+//! Storage for the snake: its cells in a ring buffer, head first, and a bitset
+//! over the grid marking the cells it covers. Growing, moving and asking
+//! whether a cell is taken are constant time, and the snake can fill the
+//! whole grid.
+
+use crate::snake::Point;
+
+/// Cells on the largest grid, the 160x128 screen at 2 pixel cells
+pub const MAX_CELLS: usize = 80 * 64;
+
+/// One bit per cell of a `width` x `height` grid
+#[derive(Clone, Debug)]
+pub struct Occupancy {
+    width: i16,
+    height: i16,
+    bits: [u32; MAX_CELLS / 32],
+}
+
+impl Occupancy {
+    pub fn new(width: i16, height: i16) -> Self {
+        assert!(width as usize * height as usize <= MAX_CELLS, "grid too large");
+        Occupancy { width, height, bits: [0; MAX_CELLS / 32] }
+    }
+
+    /// Cells outside the grid are never taken
+    pub fn get(&self, cell: Point) -> bool {
+        match self.index(cell) {
+            Some(index) => self.bits[index / 32] & (1 << (index % 32)) != 0,
+            None => false,
+        }
+    }
+
+    pub fn set(&mut self, cell: Point, taken: bool) {
+        if let Some(index) = self.index(cell) {
+            if taken {
+                self.bits[index / 32] |= 1 << (index % 32);
+            } else {
+                self.bits[index / 32] &= !(1 << (index % 32));
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+
+    fn index(&self, cell: Point) -> Option<usize> {
+        let inside = (0..self.width).contains(&cell.x) && (0..self.height).contains(&cell.y);
+        inside.then(|| cell.y as usize * self.width as usize + cell.x as usize)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Body {
+    /// Cell indices, `y * width + x`, the head at `head` and the rest
+    /// following it backwards around the ring
+    cells: [u16; MAX_CELLS],
+    head: usize,
+    len: usize,
+    occupied: Occupancy,
+}
+
+impl Body {
+    /// Empty body for a `width` x `height` grid, at most `MAX_CELLS` cells
+    pub fn new(width: i16, height: i16) -> Self {
+        Body { cells: [0; MAX_CELLS], head: 0, len: 0, occupied: Occupancy::new(width, height) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the body covers `cell`
+    pub fn contains(&self, cell: Point) -> bool {
+        self.occupied.get(cell)
+    }
+
+    pub fn head(&self) -> Option<Point> {
+        (!self.is_empty()).then(|| self.point(self.cells[self.head]))
+    }
+
+    /// Adds a new head, `cell` has to be on the grid and not covered yet
+    pub fn push_head(&mut self, cell: Point) {
+        debug_assert!(!self.contains(cell), "body already covers {:?}", cell);
+        self.head = (self.head + 1) % MAX_CELLS;
+        self.cells[self.head] = (cell.y * self.occupied.width + cell.x) as u16;
+        self.len += 1;
+        self.occupied.set(cell, true);
+    }
+
+    pub fn pop_tail(&mut self) -> Option<Point> {
+        if self.is_empty() {
+            return None;
+        }
+        let tail = self.point(self.cells[(self.head + MAX_CELLS - (self.len - 1)) % MAX_CELLS]);
+        self.len -= 1;
+        self.occupied.set(tail, false);
+        Some(tail)
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.occupied.clear();
+    }
+
+    /// Head first
+    pub fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.len).map(move |i| self.point(self.cells[(self.head + MAX_CELLS - i) % MAX_CELLS]))
+    }
+
+    fn point(&self, index: u16) -> Point {
+        let width = self.occupied.width as u16;
+        Point { x: (index % width) as i16, y: (index / width) as i16 }
+    }
+}
+
+impl PartialEq for Body {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_keep_cells_in_order() {
+        let mut body = Body::new(10, 8);
+        for x in 0..3 {
+            body.push_head(Point { x, y: 5 });
+        }
+        assert_eq!(body.head(), Some(Point { x: 2, y: 5 }));
+
+        body.push_head(Point { x: 2, y: 6 });
+        assert_eq!(body.pop_tail(), Some(Point { x: 0, y: 5 }));
+        let cells: [Point; 3] = [Point { x: 2, y: 6 }, Point { x: 2, y: 5 }, Point { x: 1, y: 5 }];
+        assert!(body.iter().eq(cells));
+        assert!(!body.contains(Point { x: 0, y: 5 }) && body.contains(Point { x: 1, y: 5 }));
+    }
+
+    #[test]
+    fn fills_the_largest_grid_and_wraps_around() {
+        let mut body = Body::new(80, 64);
+        let all = (0..64).flat_map(|y| (0..80).map(move |x| Point { x, y }));
+        for cell in all.clone() {
+            body.push_head(cell);
+        }
+        assert_eq!(body.len(), MAX_CELLS);
+        assert!(all.clone().all(|cell| body.contains(cell)));
+
+        // Round the ring a second time
+        for cell in all {
+            assert_eq!(body.pop_tail(), Some(cell));
+            body.push_head(cell);
+        }
+        assert_eq!(body.head(), Some(Point { x: 79, y: 63 }));
+        assert!(!body.contains(Point { x: 80, y: 0 }) && !body.contains(Point { x: -1, y: 3 }));
+    }
+
+    #[test]
+    fn occupancy_tracks_single_cells() {
+        let mut grid = Occupancy::new(33, 3);
+        grid.set(Point { x: 32, y: 0 }, true);
+        grid.set(Point { x: 0, y: 1 }, true);
+        assert!(grid.get(Point { x: 32, y: 0 }) && grid.get(Point { x: 0, y: 1 }));
+        assert!(!grid.get(Point { x: 31, y: 0 }) && !grid.get(Point { x: 1, y: 1 }));
+
+        grid.set(Point { x: 32, y: 0 }, false);
+        assert!(!grid.get(Point { x: 32, y: 0 }) && grid.get(Point { x: 0, y: 1 }));
+    }
+}
@@ -0,0 +1,202 @@
+//! Levels for the Snake campaign in a compact byte format. They are parsed in
+//! place, so `LEVELS` stays in flash and nothing is copied to RAM.
+//!
+//! A level is a 6 byte header followed by its walls:
+//!
+//! | byte | meaning                                        |
+//! |------|------------------------------------------------|
+//! | 0    | food to eat before the next level              |
+//! | 1    | tick period in milliseconds                    |
+//! | 2, 3 | start cell of the head, x then y               |
+//! | 4    | start direction: 0 up, 1 down, 2 left, 3 right |
+//! | 5    | number of walls                                |
+//!
+//! Every wall is a rectangle of cells, 4 bytes: x, y, width and height. The
+//! snake starts in a straight line behind its head. `LEVELS` holds the
+//! campaign's levels back to back, made for the 39 x 28 grid of the inset
+//! arena.
+
+use crate::snake::{Direction, Point, SNAKE_INITIAL_LENGTH};
+
+const HEADER_SIZE: usize = 6;
+const WALL_SIZE: usize = 4;
+
+#[rustfmt::skip]
+pub static LEVELS: &[u8] = &[
+    // Open field
+    5, 60, 19, 14, 2, 0,
+    // Four posts
+    8, 55, 19, 14, 2, 4,
+    9, 6, 2, 2,   28, 6, 2, 2,   9, 20, 2, 2,   28, 20, 2, 2,
+    // Two bars
+    10, 50, 19, 14, 2, 2,
+    8, 7, 23, 1,   8, 20, 23, 1,
+    // Cross
+    12, 45, 19, 14, 2, 4,
+    19, 2, 1, 8,   19, 20, 1, 7,   2, 14, 9, 1,   28, 14, 9, 1,
+    // Box with four doors
+    15, 40, 19, 14, 3, 6,
+    6, 5, 12, 1,   21, 5, 12, 1,   6, 22, 12, 1,   21, 22, 12, 1,   6, 8, 1, 12,   32, 8, 1, 12,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelError {
+    /// The bytes end inside a level
+    Truncated,
+    /// Direction byte other than 0 to 3
+    BadDirection(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wall {
+    pub x: i16,
+    pub y: i16,
+    pub width: i16,
+    pub height: i16,
+}
+
+impl Wall {
+    pub fn cells(self) -> impl Iterator<Item = Point> {
+        (self.y..self.y + self.height).flat_map(move |y| (self.x..self.x + self.width).map(move |x| Point { x, y }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Level<'a> {
+    pub food_target: u8,
+    pub tick_period_us: u32,
+    pub start: Point,
+    pub direction: Direction,
+    walls: &'a [u8],
+}
+
+impl<'a> Level<'a> {
+    /// Parses the level at the start of `bytes` and returns it together with
+    /// the bytes after it
+    pub fn parse(bytes: &'a [u8]) -> Result<(Self, &'a [u8]), LevelError> {
+        let header = bytes.get(..HEADER_SIZE).ok_or(LevelError::Truncated)?;
+        let direction = match header[4] {
+            0 => Direction::Up,
+            1 => Direction::Down,
+            2 => Direction::Left,
+            3 => Direction::Right,
+            other => return Err(LevelError::BadDirection(other)),
+        };
+        let end = HEADER_SIZE + header[5] as usize * WALL_SIZE;
+        let walls = bytes.get(HEADER_SIZE..end).ok_or(LevelError::Truncated)?;
+
+        let level = Level {
+            food_target: header[0],
+            tick_period_us: header[1] as u32 * 1000,
+            start: Point { x: header[2] as i16, y: header[3] as i16 },
+            direction,
+            walls,
+        };
+        Ok((level, &bytes[end..]))
+    }
+
+    pub fn walls(&self) -> impl Iterator<Item = Wall> + 'a {
+        self.walls.chunks_exact(WALL_SIZE).map(|wall| Wall {
+            x: wall[0] as i16,
+            y: wall[1] as i16,
+            width: wall[2] as i16,
+            height: wall[3] as i16,
+        })
+    }
+
+    /// Cells the snake starts on, head first
+    pub fn start_cells(&self) -> impl Iterator<Item = Point> {
+        let (dx, dy) = self.direction.delta();
+        let start = self.start;
+        (0..SNAKE_INITIAL_LENGTH as i16).map(move |i| Point { x: start.x - i * dx, y: start.y - i * dy })
+    }
+
+    /// Whether the walls and the starting snake lie on a `width` x `height`
+    /// grid and the snake is clear of the walls
+    pub fn fits(&self, width: i16, height: i16) -> bool {
+        let on_grid = |cell: Point| (0..width).contains(&cell.x) && (0..height).contains(&cell.y);
+        self.walls().flat_map(Wall::cells).all(on_grid)
+            && self.start_cells().all(|cell| on_grid(cell) && !self.walls().flat_map(Wall::cells).any(|wall| wall == cell))
+    }
+}
+
+/// The levels in `bytes` in order, a malformed one ends the list
+pub struct Levels<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Levels<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Levels { rest: bytes }
+    }
+}
+
+impl<'a> Iterator for Levels<'a> {
+    type Item = Result<Level<'a>, LevelError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        match Level::parse(self.rest) {
+            Ok((level, rest)) => {
+                self.rest = rest;
+                Some(Ok(level))
+            }
+            Err(error) => {
+                self.rest = &[];
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Level `index` of the campaign, `None` past the last one
+pub fn campaign_level(index: u8) -> Option<Level<'static>> {
+    Levels::new(LEVELS).nth(index as usize)?.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_header_and_walls() {
+        let bytes = [7, 40, 3, 4, 3, 2, 1, 1, 2, 1, 5, 0, 1, 3, 9];
+        let (level, rest) = Level::parse(&bytes).unwrap();
+        assert_eq!(rest, &[9]);
+        assert_eq!((level.food_target, level.tick_period_us), (7, 40_000));
+        assert_eq!((level.start, level.direction), (Point { x: 3, y: 4 }, Direction::Right));
+
+        let walls = [Wall { x: 1, y: 1, width: 2, height: 1 }, Wall { x: 5, y: 0, width: 1, height: 3 }];
+        assert!(level.walls().eq(walls));
+        let starts = [Point { x: 3, y: 4 }, Point { x: 2, y: 4 }, Point { x: 1, y: 4 }];
+        assert!(level.start_cells().eq(starts));
+        assert!(level.fits(8, 8));
+        assert!(!level.fits(5, 8));
+    }
+
+    #[test]
+    fn malformed_levels_are_errors() {
+        assert_eq!(Level::parse(&[1, 2, 3]), Err(LevelError::Truncated));
+        assert_eq!(Level::parse(&[1, 50, 3, 4, 2, 1, 0, 0]), Err(LevelError::Truncated));
+        assert_eq!(Level::parse(&[1, 50, 3, 4, 7, 0]), Err(LevelError::BadDirection(7)));
+
+        let mut levels = Levels::new(&[1, 50, 3, 4, 2, 0, 1, 50]);
+        assert!(matches!(levels.next(), Some(Ok(_))));
+        assert_eq!(levels.next(), Some(Err(LevelError::Truncated)));
+        assert_eq!(levels.next(), None);
+    }
+
+    #[test]
+    fn campaign_levels_fit_the_inset_grid() {
+        let levels = Levels::new(LEVELS);
+        for (index, level) in levels.enumerate() {
+            let level = level.unwrap();
+            assert!(level.fits(39, 28), "level {} does not fit", index);
+            assert!(level.food_target > 0 && level.tick_period_us > 0);
+        }
+        assert_eq!(Levels::new(LEVELS).count(), 5);
+        assert!(campaign_level(4).is_some() && campaign_level(5).is_none());
+    }
+}
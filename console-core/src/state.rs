@@ -0,0 +1,691 @@
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+use crate::framebuffer::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::games::{Game, GAMES};
+use crate::input::{AxisEdge, ButtonEdge, InputSnapshot, JoyToPin};
+use crate::render::Render;
+use crate::scores::HighScores;
+use crate::screens::{GameOver, GameOverChoice, HighScoreScreen, InitialsEntry, PauseChoice, PauseMenu, SettingsScreen};
+use crate::settings::Settings;
+
+/// Menu entries below the games
+const HIGH_SCORES_ENTRY: usize = GAMES.len();
+const SETTINGS_ENTRY: usize = GAMES.len() + 1;
+const MENU_ENTRIES: usize = GAMES.len() + 2;
+/// Rows that fit between the title and the bottom of the screen
+const VISIBLE_ENTRIES: usize = 4;
+/// The menu only polls the stick, 10 ms keeps it responsive
+pub const MENU_TICK_PERIOD_US: u32 = 10_000;
+
+/// Scrollable list of the registered games followed by "High Scores" and
+/// "Settings"
+pub struct Menu {
+    pub selected: usize,
+    /// First entry shown, the list scrolls to keep `selected` on screen
+    pub first_visible: usize,
+    stick: AxisEdge,
+    button: ButtonEdge,
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        Menu { selected: 0, first_visible: 0, stick: AxisEdge::default(), button: ButtonEdge::held() }
+    }
+
+    /// Moves the cursor one entry per push of the vertical stick, returns
+    /// true when the screen needs a redraw
+    pub fn navigate(&mut self, joy_val: u16) -> bool {
+        match self.stick.step(joy_val) {
+            1 if self.selected + 1 < MENU_ENTRIES => self.selected += 1,
+            -1 if self.selected > 0 => self.selected -= 1,
+            _ => return false,
+        }
+
+        if self.selected < self.first_visible {
+            self.first_visible = self.selected;
+        } else if self.selected >= self.first_visible + VISIBLE_ENTRIES {
+            self.first_visible = self.selected + 1 - VISIBLE_ENTRIES;
+        }
+        true
+    }
+
+    /// Returns true when the screen needs a redraw
+    pub fn handle_input(&mut self, input: &InputSnapshot) -> bool {
+        self.navigate(input.axis(JoyToPin::JoyY1))
+    }
+
+    /// Opens the selected entry once the first button is pressed, games are
+    /// seeded from the noise on the first stick
+    pub fn confirm(
+        &mut self,
+        input: &InputSnapshot,
+        scores: &HighScores,
+        settings: &Settings,
+        width: i16,
+        height: i16,
+    ) -> Option<CurrentState> {
+        if !self.button.pressed(input.button1) {
+            return None;
+        }
+
+        match self.selected {
+            HIGH_SCORES_ENTRY => Some(CurrentState::HighScores(HighScoreScreen::new(scores, 0, None))),
+            SETTINGS_ENTRY => Some(CurrentState::Settings(SettingsScreen::new(*settings))),
+            _ => Some(self.start_game(width, height, input.axis(JoyToPin::JoyX1) as u64, settings, scores)),
+        }
+    }
+
+    /// Starts the selected game, which must be one of `GAMES`
+    pub fn start_game(&self, width: i16, height: i16, seed: u64, settings: &Settings, saved: &HighScores) -> CurrentState {
+        CurrentState::start_game(self.selected, width, height, seed, settings, saved)
+    }
+
+    fn entry_name(index: usize) -> &'static str {
+        match index {
+            HIGH_SCORES_ENTRY => "High Scores",
+            SETTINGS_ENTRY => "Settings",
+            _ => GAMES[index].name,
+        }
+    }
+}
+
+impl Render for Menu {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+        Text::new("Select Game", Point::new(40, 20), style).draw(target)?;
+
+        let last_visible = MENU_ENTRIES.min(self.first_visible + VISIBLE_ENTRIES);
+        for (row, i) in (self.first_visible..last_visible).enumerate() {
+            let y = 50 + 20 * row as i32;
+            let cursor = if i == self.selected { ">" } else { " " };
+            Text::new(cursor, Point::new(40, y), style).draw(target)?;
+            Text::new(Self::entry_name(i), Point::new(52, y), style).draw(target)?;
+        }
+
+        // More entries above or below the visible ones
+        if self.first_visible > 0 {
+            Text::new("^", Point::new(148, 50), style).draw(target)?;
+        }
+        if last_visible < MENU_ENTRIES {
+            Text::new("v", Point::new(148, 50 + 20 * (VISIBLE_ENTRIES as i32 - 1)), style).draw(target)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Menu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// There is no heap on the console and only one state is alive at a time
+#[allow(clippy::large_enum_variant)]
+pub enum CurrentState {
+    Menu(Menu),
+    /// `id` is the game's index in `GAMES`, `seed` is kept for restarts.
+    /// While `pause` is open the game is frozen and `played_us` stops.
+    Playing {
+        id: usize,
+        seed: u64,
+        game: Game,
+        played_us: u64,
+        pause: Option<PauseMenu>,
+        pause_button: ButtonEdge,
+    },
+    /// Attract mode, see `GameEntry::demo`
+    Demo { id: usize, seed: u64, game: Game, leave_button: ButtonEdge },
+    GameOver(GameOver),
+    HighScores(HighScoreScreen),
+    EnterInitials(InitialsEntry),
+    Settings(SettingsScreen),
+}
+
+impl CurrentState {
+    /// Fresh game `id` from `GAMES`
+    pub fn start_game(
+        id: usize,
+        width: i16,
+        height: i16,
+        seed: u64,
+        settings: &Settings,
+        saved: &HighScores,
+    ) -> CurrentState {
+        let game = (GAMES[id].start)(width, height, seed, settings, saved);
+        if GAMES[id].demo {
+            return CurrentState::Demo { id, seed, game, leave_button: ButtonEdge::held() };
+        }
+
+        CurrentState::Playing { id, seed, game, played_us: 0, pause: None, pause_button: ButtonEdge::released() }
+    }
+
+    /// How often `update` should be called for the current screen
+    pub fn tick_period_us(&self) -> u32 {
+        match self {
+            CurrentState::Playing { game, pause: None, .. } | CurrentState::Demo { game, .. } => game.tick_period_us(),
+            _ => MENU_TICK_PERIOD_US,
+        }
+    }
+
+    /// Passes the sticks read between two ticks to the running game
+    pub fn handle_input(&mut self, input: &InputSnapshot) {
+        if let CurrentState::Playing { game, pause: None, .. } = self {
+            game.handle_input(input);
+        }
+    }
+
+    /// Advances the current screen by one tick and switches screens when the
+    /// menu confirms an entry, a game has ended or a screen was dismissed.
+    /// New high scores and campaign progress are added to `scores`, see
+    /// `HighScores::take_unsaved`, and the settings screen writes back to
+    /// `settings`.
+    pub fn update(&mut self, input: &InputSnapshot, scores: &mut HighScores, settings: &mut Settings) {
+        match self {
+            CurrentState::Menu(menu) => {
+                menu.handle_input(input);
+                if let Some(state) = menu.confirm(input, scores, settings, SCREEN_WIDTH as i16, SCREEN_HEIGHT as i16) {
+                    *self = state;
+                }
+            }
+            CurrentState::Playing { id, seed, game, played_us, pause, pause_button } => {
+                // Tracked while paused too, so the press that resumes does not pause again
+                let pause_pressed = pause_button.pressed(input.button2);
+
+                if let Some(menu) = pause {
+                    match menu.update(input) {
+                        Some(PauseChoice::Resume) => *pause = None,
+                        Some(PauseChoice::Restart) => {
+                            *self = CurrentState::start_game(*id, SCREEN_WIDTH as i16, SCREEN_HEIGHT as i16, *seed, settings, scores);
+                        }
+                        Some(PauseChoice::Quit) => *self = CurrentState::Menu(Menu::new()),
+                        None => {}
+                    }
+                } else if pause_pressed {
+                    *pause = Some(PauseMenu::new());
+                } else {
+                    *played_us += game.tick_period_us() as u64;
+                    game.update(input);
+                    if let Some(level) = game.campaign_level() {
+                        scores.reach_campaign_level(level);
+                    }
+                    if let Some(state) = self.next_state(scores) {
+                        *self = state;
+                    }
+                }
+            }
+            CurrentState::Demo { id, seed, game, leave_button } => {
+                if leave_button.pressed(input.button1 || input.button2) {
+                    *self = CurrentState::Menu(Menu::new());
+                } else {
+                    game.update(input);
+                    if game.result().is_some() {
+                        *seed = seed.wrapping_mul(31).wrapping_add(1);
+                        *game = (GAMES[*id].start)(SCREEN_WIDTH as i16, SCREEN_HEIGHT as i16, *seed, settings, scores);
+                    }
+                }
+            }
+            CurrentState::GameOver(over) => {
+                if let Some(choice) = over.update(input) {
+                    let play_again = (choice == GameOverChoice::PlayAgain).then(|| over.next_seed(input));
+                    *self = if over.new_high_score {
+                        CurrentState::EnterInitials(InitialsEntry::new(over.id, over.result.score(), play_again))
+                    } else if let Some(seed) = play_again {
+                        CurrentState::start_game(over.id, SCREEN_WIDTH as i16, SCREEN_HEIGHT as i16, seed, settings, scores)
+                    } else {
+                        CurrentState::Menu(Menu::new())
+                    };
+                }
+            }
+            CurrentState::HighScores(screen) => {
+                if screen.update(input) {
+                    *self = CurrentState::Menu(Menu::new());
+                }
+            }
+            CurrentState::EnterInitials(entry) => {
+                if let Some(score) = entry.update(input) {
+                    let game = entry.game;
+                    let rank = scores.insert(game, score);
+                    *self = match entry.play_again {
+                        Some(seed) => {
+                            CurrentState::start_game(game, SCREEN_WIDTH as i16, SCREEN_HEIGHT as i16, seed, settings, scores)
+                        }
+                        None => CurrentState::HighScores(HighScoreScreen::new(scores, game, rank)),
+                    };
+                }
+            }
+            CurrentState::Settings(screen) => {
+                if screen.update(input) {
+                    *settings = screen.settings;
+                    *self = CurrentState::Menu(Menu::new());
+                }
+            }
+        }
+    }
+
+    /// Game over screen to switch to once the running game has ended, noting
+    /// whether the result makes the high score table
+    pub fn next_state(&self, scores: &HighScores) -> Option<CurrentState> {
+        match self {
+            CurrentState::Playing { id, seed, game, played_us, .. } => {
+                let result = game.result()?;
+                let new_high_score = scores.qualifies(*id, result.score());
+                Some(CurrentState::GameOver(GameOver::new(*id, *seed, result, *played_us, new_high_score)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Render for CurrentState {
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        match self {
+            CurrentState::Menu(menu) => menu.render(target),
+            CurrentState::Playing { game, pause, .. } => {
+                game.render(target)?;
+                match pause {
+                    Some(menu) => menu.render(target),
+                    None => Ok(()),
+                }
+            }
+            CurrentState::Demo { game, .. } => game.render(target),
+            CurrentState::GameOver(over) => over.render(target),
+            CurrentState::HighScores(screen) => screen.render(target),
+            CurrentState::EnterInitials(entry) => entry.render(target),
+            CurrentState::Settings(screen) => screen.render(target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{InputSource, ScriptedInput, JOY_MAX_VAL};
+    use crate::pong::{self, Controller, PlayerTurn};
+    use crate::pong_ai::Difficulty;
+    use crate::scores::{ScoreEntry, TOP_N};
+    use crate::snake::{self, Arena, Direction, Point, Snake, CELL_SIZE};
+
+    const PONG: usize = 0;
+    const SNAKE: usize = 1;
+    const PONG_VS_CPU: usize = 2;
+    const PONG_DEMO: usize = 3;
+    const SNAKE_CAMPAIGN: usize = 5;
+
+    fn playing(id: usize) -> CurrentState {
+        CurrentState::start_game(id, 160, 128, 1, &Settings::default(), &HighScores::new())
+    }
+
+    #[test]
+    fn menu_follows_vertical_stick() {
+        let mut menu = Menu::new();
+
+        assert!(!menu.navigate(JOY_MAX_VAL / 2));
+        assert_eq!(menu.selected, 0);
+
+        assert!(menu.navigate(JOY_MAX_VAL));
+        assert_eq!(menu.selected, 1);
+
+        assert!(menu.navigate(0));
+        assert_eq!(menu.selected, 0);
+    }
+
+    #[test]
+    fn menu_scrolls_and_stops_at_the_ends() {
+        let mut menu = Menu::new();
+        assert!(!menu.navigate(0));
+
+        for _ in 0..MENU_ENTRIES + 2 {
+            menu.navigate(JOY_MAX_VAL);
+            menu.navigate(JOY_MAX_VAL / 2);
+            assert!(menu.selected >= menu.first_visible);
+            assert!(menu.selected < menu.first_visible + VISIBLE_ENTRIES);
+        }
+        assert_eq!(menu.selected, SETTINGS_ENTRY);
+        assert_eq!(Menu::entry_name(menu.selected), "Settings");
+        assert_eq!(Menu::entry_name(HIGH_SCORES_ENTRY), "High Scores");
+    }
+
+    #[test]
+    fn menu_starts_selected_game() {
+        let mut menu = Menu::new();
+        assert!(matches!(menu.start_game(160, 128, 1, &Settings::default(), &HighScores::new()), CurrentState::Playing { id: PONG, game: Game::Pong(_), .. }));
+
+        menu.navigate(JOY_MAX_VAL);
+        assert!(matches!(menu.start_game(160, 128, 1, &Settings::default(), &HighScores::new()), CurrentState::Playing { id: SNAKE, game: Game::Snake(_), .. }));
+    }
+
+    #[test]
+    fn button_confirms_selection() {
+        let frames = [
+            InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL),
+            InputSnapshot::CENTERED.with_buttons(true, false),
+        ];
+        let mut input = ScriptedInput::new(&frames);
+        let mut menu = Menu::new();
+
+        let snapshot = input.read();
+        assert!(menu.handle_input(&snapshot));
+        assert!(menu.confirm(&snapshot, &HighScores::new(), &Settings::default(), 160, 128).is_none());
+
+        let snapshot = input.read();
+        menu.handle_input(&snapshot);
+        let state = menu.confirm(&snapshot, &HighScores::new(), &Settings::default(), 160, 128);
+        assert!(matches!(state, Some(CurrentState::Playing { id: SNAKE, .. })));
+    }
+
+    fn dismiss(state: &mut CurrentState, scores: &mut HighScores, settings: &mut Settings, choice: &InputSnapshot) {
+        state.update(&InputSnapshot::CENTERED, scores, settings);
+        state.update(choice, scores, settings);
+        state.update(&InputSnapshot::CENTERED, scores, settings);
+        state.update(&InputSnapshot::CENTERED.with_buttons(true, false), scores, settings);
+    }
+
+    #[test]
+    fn finished_game_returns_to_menu() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        for _ in 0..TOP_N {
+            scores.insert(SNAKE, ScoreEntry { initials: *b"TOP", score: 100 });
+        }
+
+        let mut state = playing(SNAKE);
+        assert!(state.next_state(&scores).is_none());
+
+        if let CurrentState::Playing { game: Game::Snake(ref mut snake), .. } = state {
+            snake.alive = false;
+        }
+        let mut state = state.next_state(&scores).unwrap();
+        assert!(matches!(state, CurrentState::GameOver(GameOver { id: SNAKE, new_high_score: false, .. })));
+
+        dismiss(&mut state, &mut scores, &mut settings, &InputSnapshot::CENTERED);
+        assert!(matches!(state, CurrentState::Menu(_)));
+    }
+
+    #[test]
+    fn crashing_straight_away_is_no_high_score() {
+        let scores = HighScores::new();
+        let mut state = playing(SNAKE);
+        if let CurrentState::Playing { game: Game::Snake(ref mut snake), .. } = state {
+            snake.alive = false;
+        }
+
+        let state = state.next_state(&scores).unwrap();
+        assert!(matches!(state, CurrentState::GameOver(GameOver { new_high_score: false, .. })));
+    }
+
+    #[test]
+    fn qualifying_game_asks_for_initials() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = playing(SNAKE);
+        if let CurrentState::Playing { game: Game::Snake(ref mut snake), .. } = state {
+            snake.alive = false;
+            snake.score += 12;
+        }
+
+        let mut state = state.next_state(&scores).unwrap();
+        assert!(matches!(state, CurrentState::GameOver(GameOver { new_high_score: true, .. })));
+
+        dismiss(&mut state, &mut scores, &mut settings, &InputSnapshot::CENTERED);
+        assert!(matches!(state, CurrentState::EnterInitials(InitialsEntry { game: SNAKE, score: 12, .. })));
+
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        state.update(&InputSnapshot::CENTERED.with_buttons(true, false), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::HighScores(HighScoreScreen { game: SNAKE, highlight: Some(0), .. })));
+        assert_eq!(scores.table(SNAKE)[0].unwrap().score, 12);
+        assert!(scores.take_unsaved());
+    }
+
+    #[test]
+    fn update_switches_screens_and_tick_rate() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = CurrentState::Menu(Menu::new());
+        assert_eq!(state.tick_period_us(), MENU_TICK_PERIOD_US);
+
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        state.update(&InputSnapshot::CENTERED.with_buttons(true, false), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Playing { id: PONG, .. }));
+        assert_eq!(state.tick_period_us(), pong::TICK_PERIOD_US);
+
+        if let CurrentState::Playing { game: Game::Pong(ref mut pong), .. } = state {
+            pong.player1_score = 11;
+        }
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::GameOver(GameOver { id: PONG, .. })));
+        assert_eq!(state.tick_period_us(), MENU_TICK_PERIOD_US);
+    }
+
+    #[test]
+    fn game_over_plays_again_after_initials() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = CurrentState::start_game(SNAKE, 160, 128, 5, &settings, &scores);
+        for _ in 0..4 {
+            state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        }
+        if let CurrentState::Playing { game: Game::Snake(ref mut snake), .. } = state {
+            snake.alive = false;
+            snake.score += 1;
+        }
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        match &state {
+            CurrentState::GameOver(over) => assert_eq!(over.played_us, 5 * snake::TICK_PERIOD_US as u64),
+            _ => panic!("no game over screen"),
+        }
+
+        let down = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        dismiss(&mut state, &mut scores, &mut settings, &down);
+        assert!(matches!(state, CurrentState::EnterInitials(InitialsEntry { play_again: Some(_), .. })));
+
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        state.update(&InputSnapshot::CENTERED.with_buttons(true, false), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Playing { id: SNAKE, played_us: 0, .. }));
+        assert!(scores.take_unsaved());
+
+        // Without a new high score the game restarts straight away
+        if let CurrentState::Playing { game: Game::Snake(ref mut snake), .. } = state {
+            snake.alive = false;
+            snake.score = 0;
+        }
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        dismiss(&mut state, &mut scores, &mut settings, &down);
+        assert!(matches!(state, CurrentState::Playing { id: SNAKE, .. }));
+        assert!(!scores.take_unsaved());
+    }
+
+    #[test]
+    fn menu_opens_high_scores() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = CurrentState::Menu(Menu::new());
+
+        let down = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        for _ in 0..HIGH_SCORES_ENTRY {
+            state.update(&down, &mut scores, &mut settings);
+            state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        }
+        state.update(&InputSnapshot::CENTERED.with_buttons(true, false), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::HighScores(_)));
+    }
+
+    #[test]
+    fn settings_apply_to_new_games() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = CurrentState::Menu(Menu { selected: SETTINGS_ENTRY, ..Menu::new() });
+        let confirm = InputSnapshot::CENTERED.with_buttons(true, false);
+
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        state.update(&confirm, &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Settings(_)));
+
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        state.update(&InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL), &mut scores, &mut settings);
+        assert_eq!(settings.cpu_difficulty, Difficulty::Normal);
+        state.update(&confirm, &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Menu(_)));
+        assert_eq!(settings.cpu_difficulty, Difficulty::Hard);
+
+        let state = CurrentState::start_game(PONG_VS_CPU, 160, 128, 1, &settings, &scores);
+        match state {
+            CurrentState::Playing { game: Game::Pong(pong), .. } => {
+                assert!(!pong.is_cpu(PlayerTurn::Player1));
+                match &pong.controllers[PlayerTurn::Player2 as usize] {
+                    Controller::Cpu(cpu) => assert_eq!(cpu.params, Difficulty::Hard.params()),
+                    Controller::Stick(_) => panic!("right paddle is not the computer"),
+                }
+            }
+            _ => panic!("not playing pong"),
+        }
+    }
+
+    #[test]
+    fn demo_loops_until_a_button_is_pressed() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = CurrentState::start_game(PONG_DEMO, 160, 128, 1, &settings, &scores);
+        assert_eq!(state.tick_period_us(), pong::TICK_PERIOD_US);
+
+        // Still holding the button that started it
+        state.update(&InputSnapshot::CENTERED.with_buttons(true, false), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Demo { .. }));
+
+        if let CurrentState::Demo { game: Game::Pong(ref mut pong), .. } = state {
+            pong.player1_score = 11;
+        }
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        match &state {
+            CurrentState::Demo { seed, game: Game::Pong(pong), .. } => {
+                assert_ne!(*seed, 1);
+                assert_eq!(pong.player1_score, 0);
+            }
+            _ => panic!("demo did not start over"),
+        }
+
+        state.update(&InputSnapshot::CENTERED.with_buttons(false, true), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Menu(_)));
+        assert!(!scores.take_unsaved());
+    }
+
+    #[test]
+    fn pause_freezes_and_resumes() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = playing(PONG);
+        let ball_x = |state: &CurrentState| match state {
+            CurrentState::Playing { game: Game::Pong(pong), .. } => pong.balls[0].x,
+            _ => panic!("not playing pong"),
+        };
+
+        state.update(&InputSnapshot::CENTERED.with_buttons(false, true), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Playing { pause: Some(_), .. }));
+        assert_eq!(state.tick_period_us(), MENU_TICK_PERIOD_US);
+
+        let frozen_x = ball_x(&state);
+        for _ in 0..5 {
+            state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        }
+        assert_eq!(ball_x(&state), frozen_x);
+
+        state.update(&InputSnapshot::CENTERED.with_buttons(false, true), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Playing { pause: None, .. }));
+
+        // Holding the button does not pause again, the game moves on
+        state.update(&InputSnapshot::CENTERED.with_buttons(false, true), &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Playing { pause: None, .. }));
+        assert_ne!(ball_x(&state), frozen_x);
+    }
+
+    #[test]
+    fn pause_restarts_with_same_seed_or_quits() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = CurrentState::start_game(SNAKE, 160, 128, 77, &settings, &scores);
+        let down = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        let pause = InputSnapshot::CENTERED.with_buttons(false, true);
+        let confirm = InputSnapshot::CENTERED.with_buttons(true, false);
+
+        for _ in 0..3 {
+            state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        }
+        state.update(&pause, &mut scores, &mut settings);
+        state.update(&down, &mut scores, &mut settings);
+        state.update(&confirm, &mut scores, &mut settings);
+
+        let fresh = Snake::for_screen(160, 128, CELL_SIZE, Arena::Walls, 77);
+        match &state {
+            CurrentState::Playing { seed: 77, game: Game::Snake(snake), pause: None, .. } => {
+                assert_eq!(snake.body, fresh.body);
+                assert_eq!(snake.rng.state(), fresh.rng.state());
+            }
+            _ => panic!("restart did not start a fresh game"),
+        }
+
+        state.update(&pause, &mut scores, &mut settings);
+        state.update(&down, &mut scores, &mut settings);
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        state.update(&down, &mut scores, &mut settings);
+        state.update(&confirm, &mut scores, &mut settings);
+        assert!(matches!(state, CurrentState::Menu(_)));
+        assert!(!scores.take_unsaved());
+    }
+
+    #[test]
+    fn campaign_progress_is_saved_and_resumed() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = CurrentState::start_game(SNAKE_CAMPAIGN, 160, 128, 3, &settings, &scores);
+        assert_eq!(state.tick_period_us(), 60_000);
+
+        if let CurrentState::Playing { game: Game::Snake(ref mut snake), .. } = state {
+            snake.load_level(2);
+        }
+        state.update(&InputSnapshot::CENTERED, &mut scores, &mut settings);
+        assert_eq!(scores.campaign_level(), 2);
+        assert!(scores.take_unsaved());
+        assert_eq!(state.tick_period_us(), 50_000);
+
+        let resumed = CurrentState::start_game(SNAKE_CAMPAIGN, 160, 128, 4, &settings, &scores);
+        if let CurrentState::Playing { game, .. } = resumed {
+            assert_eq!(game.campaign_level(), Some(2));
+        }
+    }
+
+    #[test]
+    fn turns_polled_between_ticks_reach_the_snake() {
+        let mut scores = HighScores::new();
+        let mut settings = Settings::default();
+        let mut state = playing(SNAKE);
+        let up = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyY1, JOY_MAX_VAL);
+        let right = InputSnapshot::CENTERED.with_axis(JoyToPin::JoyX1, JOY_MAX_VAL);
+        let start = match &state {
+            CurrentState::Playing { game: Game::Snake(snake), .. } => snake.head_position,
+            _ => panic!("not playing Snake"),
+        };
+
+        // Up then right inside one move, the tick only sees the stick at right
+        state.handle_input(&up);
+        state.handle_input(&right);
+        state.update(&right, &mut scores, &mut settings);
+        state.update(&right, &mut scores, &mut settings);
+
+        match &state {
+            CurrentState::Playing { game: Game::Snake(snake), .. } => {
+                assert_eq!(snake.direction, Direction::Right);
+                assert_eq!(snake.head_position, Point { x: start.x + 1, y: start.y + 1 });
+            }
+            _ => panic!("the snake did not survive the turns"),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Save data kept in two reserved flash sectors.
+//!
+//! The sectors are used as a log of fixed-size records, one 256 byte flash
+//! page each. Every save programs the next blank page of the sector holding
+//! the newest record with a higher sequence number. Once its 16 pages are
+//! used, the other sector is erased and the log carries on there, so one
+//! erase cycle covers 16 saves and the newest record is never erased before
+//! a newer one has been written. On boot the newest record with a valid CRC
+//! wins; a torn write or a power loss during an erase only loses that save,
+//! not the previous one.
+
+pub const SECTOR_SIZE: usize = 4096;
+/// Sectors used in turn
+pub const SECTORS: usize = 2;
+/// Bytes reserved for save data
+pub const SAVE_SIZE: usize = SECTORS * SECTOR_SIZE;
+pub const PAGE_SIZE: usize = 256;
+const SLOTS_PER_SECTOR: usize = SECTOR_SIZE / PAGE_SIZE;
+const SLOTS: usize = SAVE_SIZE / PAGE_SIZE;
+
+const MAGIC: u32 = 0x3145_5653; // "SVE1"
+const HEADER_SIZE: usize = 8;
+const CRC_SIZE: usize = 4;
+/// Bytes available to the caller in every record
+pub const PAYLOAD_SIZE: usize = PAGE_SIZE - HEADER_SIZE - CRC_SIZE;
+
+/// The reserved sectors, `SAVE_SIZE` bytes. Offsets are relative to the start
+/// of the first one; like NOR flash, erasing sets every bit and programming
+/// can only clear bits.
+pub trait Flash {
+    type Error;
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Erases sector `sector` (0 or 1) to `0xFF`
+    fn erase(&mut self, sector: usize) -> Result<(), Self::Error>;
+
+    /// Programs one page, `offset` is a multiple of `PAGE_SIZE`
+    fn program(&mut self, offset: usize, page: &[u8; PAGE_SIZE]) -> Result<(), Self::Error>;
+}
+
+pub struct SaveStore<F> {
+    flash: F,
+    sequence: u32,
+    /// Page of the newest record
+    newest_slot: Option<usize>,
+}
+
+impl<F: Flash> SaveStore<F> {
+    /// Scans the sector and returns the store together with the newest saved
+    /// payload, `None` on a blank or corrupted sector
+    pub fn open(mut flash: F) -> Result<(Self, Option<[u8; PAYLOAD_SIZE]>), F::Error> {
+        let mut page = [0; PAGE_SIZE];
+        let mut newest: Option<(u32, usize)> = None;
+
+        for slot in 0..SLOTS {
+            flash.read(slot * PAGE_SIZE, &mut page)?;
+            if let Some(sequence) = decode_sequence(&page) {
+                if newest.map_or(true, |(best, _)| sequence > best) {
+                    newest = Some((sequence, slot));
+                }
+            }
+        }
+
+        let mut store = SaveStore { flash, sequence: 0, newest_slot: None };
+        let payload = match newest {
+            Some((sequence, slot)) => {
+                store.sequence = sequence;
+                store.newest_slot = Some(slot);
+                store.flash.read(slot * PAGE_SIZE, &mut page)?;
+
+                let mut payload = [0; PAYLOAD_SIZE];
+                payload.copy_from_slice(&page[HEADER_SIZE..HEADER_SIZE + PAYLOAD_SIZE]);
+                Some(payload)
+            }
+            None => None,
+        };
+
+        Ok((store, payload))
+    }
+
+    pub fn save(&mut self, payload: &[u8; PAYLOAD_SIZE]) -> Result<(), F::Error> {
+        let (sector, first) = match self.newest_slot {
+            Some(slot) => (slot / SLOTS_PER_SECTOR, slot + 1),
+            None => (0, 0),
+        };
+        let slot = match self.find_blank_slot(first, (sector + 1) * SLOTS_PER_SECTOR)? {
+            Some(slot) => slot,
+            None => {
+                // The sector with the newest record stays as it is until the
+                // new one is written to the other
+                let other = (sector + 1) % SECTORS;
+                self.flash.erase(other)?;
+                other * SLOTS_PER_SECTOR
+            }
+        };
+
+        let sequence = self.sequence.wrapping_add(1);
+        let mut page = [0; PAGE_SIZE];
+        page[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        page[4..8].copy_from_slice(&sequence.to_le_bytes());
+        page[HEADER_SIZE..HEADER_SIZE + PAYLOAD_SIZE].copy_from_slice(payload);
+        let crc = crc32(&page[..PAGE_SIZE - CRC_SIZE]);
+        page[PAGE_SIZE - CRC_SIZE..].copy_from_slice(&crc.to_le_bytes());
+
+        self.flash.program(slot * PAGE_SIZE, &page)?;
+        self.sequence = sequence;
+        self.newest_slot = Some(slot);
+        Ok(())
+    }
+
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    /// First fully erased page in `first..end`, which lies after the newest
+    /// record. Pages left half written by a power loss are skipped.
+    fn find_blank_slot(&mut self, first: usize, end: usize) -> Result<Option<usize>, F::Error> {
+        let mut page = [0; PAGE_SIZE];
+        for slot in first..end {
+            self.flash.read(slot * PAGE_SIZE, &mut page)?;
+            if page.iter().all(|&byte| byte == 0xFF) {
+                return Ok(Some(slot));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn decode_sequence(page: &[u8; PAGE_SIZE]) -> Option<u32> {
+    let word = |at: usize| u32::from_le_bytes([page[at], page[at + 1], page[at + 2], page[at + 3]]);
+
+    if word(0) != MAGIC || word(PAGE_SIZE - CRC_SIZE) != crc32(&page[..PAGE_SIZE - CRC_SIZE]) {
+        return None;
+    }
+    Some(word(4))
+}
+
+/// CRC-32 (IEEE), bit by bit; it runs once per page on boot and save
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct PowerLost;
+
+    /// NOR flash in RAM that counts erase cycles and can lose power before
+    /// the next page is programmed
+    struct RamFlash {
+        bytes: [u8; SAVE_SIZE],
+        erases: usize,
+        power_cut: bool,
+    }
+
+    impl RamFlash {
+        fn new() -> Self {
+            RamFlash { bytes: [0xFF; SAVE_SIZE], erases: 0, power_cut: false }
+        }
+    }
+
+    impl Flash for RamFlash {
+        type Error = PowerLost;
+
+        fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), PowerLost> {
+            buf.copy_from_slice(&self.bytes[offset..offset + buf.len()]);
+            Ok(())
+        }
+
+        fn erase(&mut self, sector: usize) -> Result<(), PowerLost> {
+            self.bytes[sector * SECTOR_SIZE..(sector + 1) * SECTOR_SIZE].fill(0xFF);
+            self.erases += 1;
+            Ok(())
+        }
+
+        fn program(&mut self, offset: usize, page: &[u8; PAGE_SIZE]) -> Result<(), PowerLost> {
+            if self.power_cut {
+                return Err(PowerLost);
+            }
+            for (stored, &byte) in self.bytes[offset..offset + PAGE_SIZE].iter_mut().zip(page) {
+                *stored &= byte;
+            }
+            Ok(())
+        }
+    }
+
+    fn payload(value: u8) -> [u8; PAYLOAD_SIZE] {
+        [value; PAYLOAD_SIZE]
+    }
+
+    #[test]
+    fn crc_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn blank_sector_has_no_save() {
+        let (_, loaded) = SaveStore::open(RamFlash::new()).unwrap();
+        assert!(loaded.is_none());
+    }
+
+    #[test]
+    fn newest_save_survives_reopen() {
+        let (mut store, _) = SaveStore::open(RamFlash::new()).unwrap();
+        store.save(&payload(1)).unwrap();
+        store.save(&payload(2)).unwrap();
+
+        let (mut store, loaded) = SaveStore::open(store.release()).unwrap();
+        assert_eq!(loaded, Some(payload(2)));
+
+        store.save(&payload(3)).unwrap();
+        let (_, loaded) = SaveStore::open(store.release()).unwrap();
+        assert_eq!(loaded, Some(payload(3)));
+    }
+
+    #[test]
+    fn sector_is_erased_once_per_lap() {
+        let (mut store, _) = SaveStore::open(RamFlash::new()).unwrap();
+        for value in 0..SLOTS_PER_SECTOR as u8 {
+            store.save(&payload(value)).unwrap();
+        }
+        assert_eq!(store.flash.erases, 0);
+
+        store.save(&payload(100)).unwrap();
+        assert_eq!(store.flash.erases, 1);
+
+        // Round both sectors and back into the first
+        for value in 0..SLOTS as u8 {
+            store.save(&payload(value)).unwrap();
+        }
+        assert_eq!(store.flash.erases, 3);
+
+        let (_, loaded) = SaveStore::open(store.release()).unwrap();
+        assert_eq!(loaded, Some(payload(SLOTS as u8 - 1)));
+    }
+
+    #[test]
+    fn power_loss_after_erase_keeps_previous_save() {
+        let (mut store, _) = SaveStore::open(RamFlash::new()).unwrap();
+        for value in 0..SLOTS as u8 {
+            store.save(&payload(value)).unwrap();
+        }
+
+        // Both sectors are full, the next save erases the older one and
+        // the power goes before the record is programmed
+        store.flash.power_cut = true;
+        let erases = store.flash.erases;
+        assert_eq!(store.save(&payload(200)), Err(PowerLost));
+        assert_eq!(store.flash.erases, erases + 1);
+
+        let mut flash = store.release();
+        flash.power_cut = false;
+        let (_, loaded) = SaveStore::open(flash).unwrap();
+        assert_eq!(loaded, Some(payload(SLOTS as u8 - 1)));
+    }
+
+    #[test]
+    fn torn_write_falls_back_to_previous_save() {
+        let (mut store, _) = SaveStore::open(RamFlash::new()).unwrap();
+        store.save(&payload(1)).unwrap();
+        store.save(&payload(2)).unwrap();
+
+        // Power lost half way through programming the second record
+        let mut flash = store.release();
+        flash.bytes[PAGE_SIZE + 100..2 * PAGE_SIZE].fill(0xFF);
+
+        let (mut store, loaded) = SaveStore::open(flash).unwrap();
+        assert_eq!(loaded, Some(payload(1)));
+
+        // The damaged page is skipped rather than programmed over
+        store.save(&payload(3)).unwrap();
+        assert_eq!(store.newest_slot, Some(2));
+        let (_, loaded) = SaveStore::open(store.release()).unwrap();
+        assert_eq!(loaded, Some(payload(3)));
+    }
+}
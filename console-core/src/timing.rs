@@ -0,0 +1,146 @@
+//! Frame pacing shared by the firmware and the simulator.
+//!
+//! Games are updated at their own fixed tick rate, independent of how long
+//! rendering and reading the joysticks take; the caller renders once after
+//! every batch of updates and sleeps until the next tick is due.
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+use heapless::String;
+
+use crate::render::Render;
+
+/// Upper bound on catch-up updates after a slow frame; anything beyond that is
+/// dropped so the game slows down instead of freezing
+pub const MAX_UPDATES_PER_FRAME: u32 = 5;
+/// How often the sticks are read between updates, so a slow game still sees
+/// every turn made within one of its ticks, see `CurrentState::handle_input`
+pub const INPUT_POLL_PERIOD_US: u32 = 10_000;
+
+pub struct FixedStep {
+    next_tick_us: Option<u64>,
+}
+
+impl FixedStep {
+    pub const fn new() -> Self {
+        FixedStep { next_tick_us: None }
+    }
+
+    /// Number of updates due at `now_us` for a game ticking every `period_us`.
+    /// The first call always schedules exactly one update.
+    pub fn advance(&mut self, now_us: u64, period_us: u32) -> u32 {
+        let period_us = period_us.max(1) as u64;
+        let next_tick_us = *self.next_tick_us.get_or_insert(now_us);
+        if now_us < next_tick_us {
+            return 0;
+        }
+
+        let due = (now_us - next_tick_us) / period_us + 1;
+        if due > MAX_UPDATES_PER_FRAME as u64 {
+            self.next_tick_us = Some(now_us + period_us);
+            MAX_UPDATES_PER_FRAME
+        } else {
+            self.next_tick_us = Some(next_tick_us + due * period_us);
+            due as u32
+        }
+    }
+
+    /// When the next update is due, `None` before the first `advance`
+    pub fn next_tick_us(&self) -> Option<u64> {
+        self.next_tick_us
+    }
+}
+
+impl Default for FixedStep {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Time spent updating and rendering one frame, excluding the sleep
+pub struct FrameStats {
+    pub last_us: u32,
+    pub average_us: u32,
+}
+
+impl FrameStats {
+    pub const fn new() -> Self {
+        FrameStats { last_us: 0, average_us: 0 }
+    }
+
+    pub fn record(&mut self, frame_us: u32) {
+        self.last_us = frame_us;
+        self.average_us = if self.average_us == 0 {
+            frame_us
+        } else {
+            // Exponential moving average over roughly the last 8 frames
+            (self.average_us * 7 + frame_us) / 8
+        };
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Render for FrameStats {
+    /// Average frame time in milliseconds, in the bottom left corner
+    fn render<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let mut text: String<16> = String::new();
+        let _ = write!(text, "{}.{}ms", self.average_us / 1000, self.average_us / 100 % 10);
+
+        let style = MonoTextStyle::new(&FONT_6X10, Rgb565::YELLOW);
+        let bottom = target.bounding_box().size.height as i32 - 3;
+        Text::new(&text, Point::new(2, bottom), style).draw(target)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_follow_tick_rate() {
+        let mut step = FixedStep::new();
+
+        assert_eq!(step.advance(1_000, 20_000), 1);
+        assert_eq!(step.next_tick_us(), Some(21_000));
+        assert_eq!(step.advance(15_000, 20_000), 0);
+        assert_eq!(step.advance(21_000, 20_000), 1);
+
+        // A slow 65 ms frame is caught up with three updates
+        assert_eq!(step.advance(86_000, 20_000), 3);
+        assert_eq!(step.next_tick_us(), Some(101_000));
+    }
+
+    #[test]
+    fn long_stall_drops_backlog() {
+        let mut step = FixedStep::new();
+        step.advance(0, 10_000);
+
+        assert_eq!(step.advance(1_000_000, 10_000), MAX_UPDATES_PER_FRAME);
+        assert_eq!(step.next_tick_us(), Some(1_010_000));
+    }
+
+    #[test]
+    fn frame_stats_average() {
+        let mut stats = FrameStats::new();
+        stats.record(8_000);
+        assert_eq!(stats.average_us, 8_000);
+
+        stats.record(16_000);
+        assert_eq!(stats.last_us, 16_000);
+        assert_eq!(stats.average_us, 9_000);
+    }
+}
@@ -0,0 +1,107 @@
+//! Save data in the last two 4 KB sectors of the 2 MB flash, reserved in
+//! `memory.x`.
+//!
+//! Flash can not be read while it is being erased or programmed, and the
+//! firmware runs straight from it (XIP). The write itself therefore runs from
+//! RAM with interrupts disabled and only calls bootrom functions, then puts
+//! XIP back the way boot2 left it.
+
+use core::convert::Infallible;
+
+use console_core::storage::{Flash, PAGE_SIZE, SAVE_SIZE, SECTOR_SIZE};
+use hal::rom_data;
+
+const XIP_BASE: usize = 0x1000_0000;
+/// Offset of the save sectors from the start of flash, matches `SAVE` in
+/// `memory.x`. Saves from when there was only the last sector are found in
+/// the second one.
+const SAVE_OFFSET: usize = 2048 * 1024 - SAVE_SIZE;
+
+const SECTOR_ERASE_CMD: u8 = 0x20;
+
+pub struct RomFlash {
+    _private: (),
+}
+
+impl RomFlash {
+    /// There must only be one of these and nothing else may write to flash
+    pub fn new() -> Self {
+        RomFlash { _private: () }
+    }
+}
+
+impl Flash for RomFlash {
+    type Error = Infallible;
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Infallible> {
+        assert!(offset + buf.len() <= SAVE_SIZE);
+        let src = (XIP_BASE + SAVE_OFFSET + offset) as *const u8;
+        // SAFETY: the range lies inside the save sectors, which are mapped and
+        // not written while this reference exists
+        unsafe { core::ptr::copy_nonoverlapping(src, buf.as_mut_ptr(), buf.len()) };
+        Ok(())
+    }
+
+    fn erase(&mut self, sector: usize) -> Result<(), Infallible> {
+        assert!(sector * SECTOR_SIZE < SAVE_SIZE);
+        // SAFETY: only a reserved sector is touched
+        unsafe { write((SAVE_OFFSET + sector * SECTOR_SIZE) as u32, None) };
+        Ok(())
+    }
+
+    // `is_multiple_of` needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn program(&mut self, offset: usize, page: &[u8; PAGE_SIZE]) -> Result<(), Infallible> {
+        assert!(offset % PAGE_SIZE == 0 && offset < SAVE_SIZE);
+        // SAFETY: only a page of the reserved sectors is touched
+        unsafe { write((SAVE_OFFSET + offset) as u32, Some(page)) };
+        Ok(())
+    }
+}
+
+/// Function pointers resolved before XIP is turned off, the lookup itself
+/// runs from flash
+struct RomFunctions {
+    connect_internal_flash: unsafe extern "C" fn(),
+    flash_exit_xip: unsafe extern "C" fn(),
+    flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: unsafe extern "C" fn(),
+}
+
+/// Erases the sector at `offset` (`page` is `None`) or programs one page
+unsafe fn write(offset: u32, page: Option<&[u8; PAGE_SIZE]>) {
+    let rom = RomFunctions {
+        connect_internal_flash: rom_data::connect_internal_flash::ptr(),
+        flash_exit_xip: rom_data::flash_exit_xip::ptr(),
+        flash_range_erase: rom_data::flash_range_erase::ptr(),
+        flash_range_program: rom_data::flash_range_program::ptr(),
+        flash_flush_cache: rom_data::flash_flush_cache::ptr(),
+    };
+
+    // boot2 configures the fast QSPI read mode, a copy in RAM restores it
+    let mut boot2 = [0u32; 64];
+    core::ptr::copy_nonoverlapping(XIP_BASE as *const u32, boot2.as_mut_ptr(), boot2.len());
+
+    cortex_m::interrupt::free(|_| {
+        write_from_ram(&rom, offset, page.map_or(core::ptr::null(), |page| page.as_ptr()), boot2.as_ptr());
+    });
+}
+
+/// Must not touch flash at all: everything it needs is on the stack or in ROM
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn write_from_ram(rom: &RomFunctions, offset: u32, data: *const u8, boot2: *const u32) {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    if data.is_null() {
+        (rom.flash_range_erase)(offset, SECTOR_SIZE, SECTOR_SIZE as u32, SECTOR_ERASE_CMD);
+    } else {
+        (rom.flash_range_program)(offset, data, PAGE_SIZE);
+    }
+    (rom.flash_flush_cache)();
+
+    // Thumb code, hence the set low bit
+    let boot2: extern "C" fn() = core::mem::transmute((boot2 as usize) | 1);
+    boot2();
+}
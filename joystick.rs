@@ -0,0 +1,69 @@
+//! Both joysticks on the board.
+//!
+//! The Pico has only three ADC inputs, so the four analog axes go through a
+//! 4-channel multiplexer: GPIO10/GPIO11 select the channel and GPIO26 reads it.
+//! The buttons are on GPIO8 and GPIO9 with pull-ups, so they read low when pressed.
+
+use console_core::input::{InputSnapshot, InputSource, JoyToPin};
+use embedded_hal::adc::OneShot;
+use embedded_hal::digital::v2::{InputPin, OutputPin, PinState};
+use hal::gpio::bank0::{Gpio10, Gpio11, Gpio26, Gpio8, Gpio9};
+use hal::gpio::{FloatingInput, Pin, PullUpInput, PushPullOutput};
+use hal::Adc;
+
+/// Time the mux output needs to settle after switching channels
+const MUX_SETTLE_CYCLES: u32 = 10000;
+
+pub struct MuxJoysticks {
+    adc: Adc,
+    select_0: Pin<Gpio10, PushPullOutput>,
+    select_1: Pin<Gpio11, PushPullOutput>,
+    joy_adc: Pin<Gpio26, FloatingInput>,
+    button1: Pin<Gpio8, PullUpInput>,
+    button2: Pin<Gpio9, PullUpInput>,
+}
+
+impl MuxJoysticks {
+    pub fn new(
+        adc: Adc,
+        select_0: Pin<Gpio10, PushPullOutput>,
+        select_1: Pin<Gpio11, PushPullOutput>,
+        joy_adc: Pin<Gpio26, FloatingInput>,
+        button1: Pin<Gpio8, PullUpInput>,
+        button2: Pin<Gpio9, PullUpInput>,
+    ) -> Self {
+        MuxJoysticks { adc, select_0, select_1, joy_adc, button1, button2 }
+    }
+
+    pub fn read_axis(&mut self, joy: JoyToPin) -> u16 {
+        let (s0, s1) = match joy {
+            JoyToPin::JoyX1 => (false, false),
+            JoyToPin::JoyY1 => (true, false),
+            JoyToPin::JoyX2 => (false, true),
+            JoyToPin::JoyY2 => (true, true),
+        };
+
+        // GPIO writes on the RP2040 cannot fail
+        let _ = self.select_0.set_state(PinState::from(s0));
+        let _ = self.select_1.set_state(PinState::from(s1));
+
+        cortex_m::asm::delay(MUX_SETTLE_CYCLES);
+
+        self.adc.read(&mut self.joy_adc).unwrap_or(0)
+    }
+}
+
+impl InputSource for MuxJoysticks {
+    fn read(&mut self) -> InputSnapshot {
+        InputSnapshot {
+            axes: [
+                self.read_axis(JoyToPin::JoyX1),
+                self.read_axis(JoyToPin::JoyY1),
+                self.read_axis(JoyToPin::JoyX2),
+                self.read_axis(JoyToPin::JoyY2),
+            ],
+            button1: self.button1.is_low().unwrap_or(false),
+            button2: self.button2.is_low().unwrap_or(false),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Double-buffered rendering for the LCD.
+//!
+//! Games draw the whole scene into one of two RAM framebuffers while DMA
+//! channel 0 streams the other one to the ST7735 over SPI0, so the CPU does not
+//! block on SPI writes and the panel only ever receives finished frames.
+//! Two 160x128 RGB565 buffers take 80 KB of the 256 KB of RAM.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use console_core::framebuffer::{Framebuffer, SCREEN_HEIGHT, SCREEN_WIDTH};
+use console_core::render::Render;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use hal::dma::DREQ_SPI0_TX;
+use hal::gpio::bank0::{Gpio13, Gpio14};
+use hal::gpio::{Pin, PushPullOutput};
+use hal::pac;
+use hal::spi::{Enabled, Spi};
+
+pub type Lcd = st7735_lcd::ST7735<
+    Spi<Enabled, pac::SPI0, 8>,
+    Pin<Gpio13, PushPullOutput>,
+    Pin<Gpio14, PushPullOutput>,
+>;
+
+const DMA_CHANNEL: usize = 0;
+
+pub struct DmaDisplay {
+    lcd: Lcd,
+    dma: pac::DMA,
+    buffers: &'static mut [Framebuffer; 2],
+    back: usize,
+    in_flight: bool,
+}
+
+impl DmaDisplay {
+    pub fn new(
+        lcd: Lcd,
+        dma: pac::DMA,
+        resets: &mut pac::RESETS,
+        buffers: &'static mut [Framebuffer; 2],
+    ) -> Self {
+        resets.reset.modify(|_, w| w.dma().clear_bit());
+        while resets.reset_done.read().dma().bit_is_clear() {}
+
+        // Let SPI0 request data whenever its TX FIFO has room
+        spi0().sspdmacr.modify(|_, w| w.txdmae().set_bit());
+
+        DmaDisplay { lcd, dma, buffers, back: 0, in_flight: false }
+    }
+
+    /// Renders `scene` into the back buffer, waits for the previous frame to
+    /// finish streaming and starts sending the new one
+    pub fn draw_frame<R>(&mut self, scene: &R) -> Result<(), ()>
+    where
+        R: Render + ?Sized,
+    {
+        let back = &mut self.buffers[self.back];
+        back.clear(Rgb565::BLACK).unwrap();
+        match scene.render(back) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+
+        self.wait();
+
+        // Sets the window to the whole panel, sends RAMWR and leaves D/C in
+        // data mode, everything after that is pixel data
+        self.lcd.set_pixels(
+            0,
+            0,
+            SCREEN_WIDTH as u16 - 1,
+            SCREEN_HEIGHT as u16 - 1,
+            core::iter::empty(),
+        )?;
+        self.start(self.back);
+        self.back ^= 1;
+        Ok(())
+    }
+
+    /// Blocks until the frame being streamed has fully left the SPI shifter
+    pub fn wait(&mut self) {
+        if !self.in_flight {
+            return;
+        }
+
+        let channel = &self.dma.ch[DMA_CHANNEL];
+        while channel.ch_ctrl_trig.read().busy().bit_is_set() {}
+
+        let spi = spi0();
+        while spi.sspsr.read().bsy().bit_is_set() {}
+
+        // The blocking SPI driver reads back one byte per byte written, drop
+        // what was clocked in during the transfer so it stays in step
+        while spi.sspsr.read().rne().bit_is_set() {
+            let _ = spi.sspdr.read();
+        }
+        spi.sspicr.write(|w| w.roric().set_bit());
+
+        compiler_fence(Ordering::SeqCst);
+        self.in_flight = false;
+    }
+
+    fn start(&mut self, index: usize) {
+        let bytes = self.buffers[index].as_bytes();
+        let channel = &self.dma.ch[DMA_CHANNEL];
+
+        compiler_fence(Ordering::SeqCst);
+        channel.ch_read_addr.write(|w| unsafe { w.bits(bytes.as_ptr() as u32) });
+        channel.ch_write_addr.write(|w| unsafe { w.bits(spi0().sspdr.as_ptr() as u32) });
+        channel.ch_trans_count.write(|w| unsafe { w.bits(bytes.len() as u32) });
+        channel.ch_ctrl_trig.write(|w| unsafe {
+            w.data_size().size_byte()
+                .incr_read().set_bit()
+                .incr_write().clear_bit()
+                .treq_sel().bits(DREQ_SPI0_TX)
+                .chain_to().bits(DMA_CHANNEL as u8)
+                .en().set_bit()
+        });
+        self.in_flight = true;
+    }
+}
+
+/// SPI0 registers. The peripheral itself is owned by the LCD driver; only
+/// the DMA enable, status and data registers are touched here, and only while
+/// the driver is not using the bus.
+fn spi0() -> &'static pac::spi0::RegisterBlock {
+    unsafe { &*pac::SPI0::ptr() }
+}
@@ -1,6 +1,8 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* The last two 4K sectors are reserved for save data, see flash_store.rs */
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100 - 8K
+    SAVE  : ORIGIN = 0x101FE000, LENGTH = 8K
     RAM   : ORIGIN = 0x20000000, LENGTH = 256K
 }
 
@@ -0,0 +1,374 @@
+//! Desktop simulator for the console.
+//!
+//! Drives the same `Pong` and `Snake` engines and the same menu flow as
+//! `main.rs`, but renders the 160x128 panel into a terminal and reads the
+//! joysticks from the keyboard, so game tweaks can be tried without a board.
+//!
+//! Build and run it for the host:
+//!
+//! `cargo run --bin simulator --features simulator --target x86_64-unknown-linux-gnu`
+//!
+//! The terminal has to be at least 160 columns by 64 rows (two panel rows are
+//! packed into one character cell). High scores are kept in
+//! `simulator-save.bin` in the working directory, in the same format as the
+//! save sectors on the board.
+//!
+//! Keys:
+//! - `W`/`A`/`S`/`D`: joystick 1 (`JoyY1`/`JoyX1`)
+//! - arrow keys: joystick 2 (`JoyY2`/`JoyX2`)
+//! - `Space`: joystick button 1, `Enter`: joystick button 2
+//! - `F`: show the frame time
+//! - `Q`/`Esc`: quit
+
+extern crate console_core;
+extern crate crossterm;
+extern crate embedded_graphics;
+extern crate itoa;
+
+use std::fs;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+
+use embedded_graphics::pixelcolor::{Rgb565, Rgb888};
+use embedded_graphics::prelude::*;
+use console_core::framebuffer::{Framebuffer, SCREEN_HEIGHT, SCREEN_WIDTH};
+use console_core::input::{InputSnapshot, InputSource, JoyToPin, JOY_CENTER_VAL, JOY_MAX_VAL};
+use console_core::render::Render;
+use console_core::scores::HighScores;
+use console_core::settings::Settings;
+use console_core::state::{CurrentState, Menu};
+use console_core::storage::{Flash, SaveStore, PAGE_SIZE, SAVE_SIZE, SECTOR_SIZE};
+use console_core::timing::{FixedStep, FrameStats, INPUT_POLL_PERIOD_US};
+
+/// Most terminals only report key presses (and auto-repeats), never releases,
+/// so a key counts as held for this long after its last press event.
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+const SAVE_FILE: &str = "simulator-save.bin";
+
+/// Terminal stand-in for the ST7735 panel.
+struct TerminalScreen {
+    presented: Vec<(Rgb565, Rgb565)>,
+}
+
+impl TerminalScreen {
+    fn new() -> Self {
+        TerminalScreen {
+            presented: Vec::new(),
+        }
+    }
+
+    /// Writes the cells that changed since the last call, using a half block
+    /// so every character cell shows two panel rows.
+    fn present<W: Write>(&mut self, framebuffer: &Framebuffer, out: &mut W) -> io::Result<()> {
+        let cells = SCREEN_WIDTH * SCREEN_HEIGHT / 2;
+        let full_redraw = self.presented.len() != cells;
+        if full_redraw {
+            self.presented = vec![(Rgb565::BLACK, Rgb565::BLACK); cells];
+        }
+
+        let mut cursor_at = None;
+        for row in 0..SCREEN_HEIGHT / 2 {
+            for col in 0..SCREEN_WIDTH {
+                let upper = framebuffer.pixel(col as i32, row as i32 * 2).unwrap();
+                let lower = framebuffer.pixel(col as i32, row as i32 * 2 + 1).unwrap();
+                let cell = &mut self.presented[row * SCREEN_WIDTH + col];
+                if !full_redraw && *cell == (upper, lower) {
+                    continue;
+                }
+                *cell = (upper, lower);
+
+                if cursor_at != Some((col, row)) {
+                    queue!(out, cursor::MoveTo(col as u16, row as u16))?;
+                }
+                queue!(
+                    out,
+                    SetForegroundColor(terminal_color(upper)),
+                    SetBackgroundColor(terminal_color(lower)),
+                    Print('\u{2580}')
+                )?;
+                cursor_at = Some((col + 1, row));
+            }
+        }
+        out.flush()
+    }
+}
+
+fn terminal_color(color: Rgb565) -> Color {
+    let color = Rgb888::from(color);
+    Color::Rgb {
+        r: color.r(),
+        g: color.g(),
+        b: color.b(),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Key {
+    X1Low,
+    X1High,
+    Y1Low,
+    Y1High,
+    X2Low,
+    X2High,
+    Y2Low,
+    Y2High,
+    Button1,
+    Button2,
+}
+
+const KEY_COUNT: usize = 10;
+
+/// Keyboard stand-in for the two analog joysticks and their buttons.
+struct Keyboard {
+    held_until: [Option<Instant>; KEY_COUNT],
+    reports_release: bool,
+    show_frame_time: bool,
+    quit: bool,
+}
+
+impl Keyboard {
+    fn new(reports_release: bool) -> Self {
+        Keyboard {
+            held_until: [None; KEY_COUNT],
+            reports_release,
+            show_frame_time: false,
+            quit: false,
+        }
+    }
+
+    fn poll(&mut self) -> io::Result<()> {
+        while event::poll(Duration::ZERO)? {
+            let key_event = match event::read()? {
+                Event::Key(key_event) => key_event,
+                _ => continue,
+            };
+
+            let key = match key_event.code {
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    self.quit = true;
+                    continue;
+                }
+                KeyCode::Char('f') => {
+                    if key_event.kind == KeyEventKind::Press {
+                        self.show_frame_time = !self.show_frame_time;
+                    }
+                    continue;
+                }
+                KeyCode::Char('a') => Key::X1Low,
+                KeyCode::Char('d') => Key::X1High,
+                KeyCode::Char('w') => Key::Y1Low,
+                KeyCode::Char('s') => Key::Y1High,
+                KeyCode::Left => Key::X2Low,
+                KeyCode::Right => Key::X2High,
+                KeyCode::Up => Key::Y2Low,
+                KeyCode::Down => Key::Y2High,
+                KeyCode::Char(' ') => Key::Button1,
+                KeyCode::Enter => Key::Button2,
+                _ => continue,
+            };
+
+            self.held_until[key as usize] = match key_event.kind {
+                KeyEventKind::Release => None,
+                // Held until the matching release event arrives
+                _ if self.reports_release => Some(Instant::now() + Duration::from_secs(3600)),
+                _ => Some(Instant::now() + KEY_HOLD),
+            };
+        }
+        Ok(())
+    }
+
+    fn is_held(&self, key: Key) -> bool {
+        match self.held_until[key as usize] {
+            Some(until) => Instant::now() < until,
+            None => false,
+        }
+    }
+
+    /// Same scale as the 12-bit ADC reading on the board.
+    fn read_axis(&self, joy: JoyToPin) -> u16 {
+        let (low, high) = match joy {
+            JoyToPin::JoyX1 => (Key::X1Low, Key::X1High),
+            JoyToPin::JoyY1 => (Key::Y1Low, Key::Y1High),
+            JoyToPin::JoyX2 => (Key::X2Low, Key::X2High),
+            JoyToPin::JoyY2 => (Key::Y2Low, Key::Y2High),
+        };
+
+        match (self.is_held(low), self.is_held(high)) {
+            (true, false) => 0,
+            (false, true) => JOY_MAX_VAL,
+            _ => JOY_CENTER_VAL,
+        }
+    }
+}
+
+impl InputSource for Keyboard {
+    fn read(&mut self) -> InputSnapshot {
+        InputSnapshot {
+            axes: [
+                self.read_axis(JoyToPin::JoyX1),
+                self.read_axis(JoyToPin::JoyY1),
+                self.read_axis(JoyToPin::JoyX2),
+                self.read_axis(JoyToPin::JoyY2),
+            ],
+            button1: self.is_held(Key::Button1),
+            button2: self.is_held(Key::Button2),
+        }
+    }
+}
+
+/// File stand-in for the save sectors in flash, rewritten on every change.
+struct FileFlash {
+    bytes: Vec<u8>,
+}
+
+impl FileFlash {
+    fn open() -> Self {
+        let mut bytes = vec![0xFF; SAVE_SIZE];
+        match fs::read(SAVE_FILE) {
+            Ok(saved) if saved.len() == SAVE_SIZE => bytes = saved,
+            // A file from when there was one sector, it is the last one on the board
+            Ok(saved) if saved.len() == SECTOR_SIZE => bytes[SAVE_SIZE - SECTOR_SIZE..].copy_from_slice(&saved),
+            _ => {}
+        }
+        FileFlash { bytes }
+    }
+}
+
+impl Flash for FileFlash {
+    type Error = io::Error;
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<()> {
+        buf.copy_from_slice(&self.bytes[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    fn erase(&mut self, sector: usize) -> io::Result<()> {
+        self.bytes[sector * SECTOR_SIZE..(sector + 1) * SECTOR_SIZE].fill(0xFF);
+        fs::write(SAVE_FILE, &self.bytes)
+    }
+
+    fn program(&mut self, offset: usize, page: &[u8; PAGE_SIZE]) -> io::Result<()> {
+        // Like NOR flash, programming only clears bits
+        for (stored, &byte) in self.bytes[offset..offset + PAGE_SIZE].iter_mut().zip(page) {
+            *stored &= byte;
+        }
+        fs::write(SAVE_FILE, &self.bytes)
+    }
+}
+
+/// Puts the terminal into raw mode on the alternate screen and restores it on
+/// drop, also when the simulator panics.
+struct TerminalGuard {
+    reports_release: bool,
+}
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        queue!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+        let reports_release = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_release {
+            queue!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        stdout.flush()?;
+
+        Ok(TerminalGuard { reports_release })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        if self.reports_release {
+            let _ = queue!(stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = queue!(stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn main() -> io::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    // Checked before the alternate screen opens, it would hide the message
+    if (columns as usize) < SCREEN_WIDTH || (rows as usize) < SCREEN_HEIGHT / 2 {
+        let message = format!(
+            "terminal is {}x{}, the simulator needs at least {}x{}",
+            columns,
+            rows,
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT / 2
+        );
+        return Err(io::Error::other(message));
+    }
+
+    let (mut save_store, saved) = SaveStore::open(FileFlash::open())?;
+    let mut high_scores = saved.map(|payload| HighScores::from_payload(&payload)).unwrap_or_default();
+    let mut settings = Settings::default();
+
+    let guard = TerminalGuard::enter()?;
+    let mut keyboard = Keyboard::new(guard.reports_release);
+    let mut framebuffer: Box<Framebuffer> = Box::default();
+    let mut screen = TerminalScreen::new();
+    let mut stdout = io::stdout();
+
+    let clock = Instant::now();
+    let mut step = FixedStep::new();
+    let mut poll = FixedStep::new();
+    let mut stats = FrameStats::new();
+    let mut current_state = CurrentState::Menu(Menu::new());
+    loop {
+        keyboard.poll()?;
+        if keyboard.quit {
+            break;
+        }
+
+        let frame_start = clock.elapsed();
+        let updates = step.advance(frame_start.as_micros() as u64, current_state.tick_period_us());
+        if updates > 0 {
+            let input = keyboard.read();
+            for _ in 0..updates {
+                current_state.update(&input, &mut high_scores, &mut settings);
+            }
+
+            if high_scores.take_unsaved() {
+                save_store.save(&high_scores.to_payload())?;
+            }
+
+            framebuffer.clear(Rgb565::BLACK).unwrap();
+            if keyboard.show_frame_time {
+                (&current_state, &stats).render(&mut *framebuffer).unwrap();
+            } else {
+                current_state.render(&mut *framebuffer).unwrap();
+            }
+            screen.present(&framebuffer, &mut stdout)?;
+            stats.record((clock.elapsed() - frame_start).as_micros() as u32);
+        } else if poll.advance(frame_start.as_micros() as u64, INPUT_POLL_PERIOD_US) > 0 {
+            // Only the keys between updates, see `INPUT_POLL_PERIOD_US`
+            current_state.handle_input(&keyboard.read());
+        }
+
+        // Whichever comes first, the next update or the next read of the keys
+        if let Some(next_tick) = step.next_tick_us().into_iter().chain(poll.next_tick_us()).min() {
+            let now = clock.elapsed().as_micros() as u64;
+            thread::sleep(Duration::from_micros(next_tick.saturating_sub(now)));
+        }
+    }
+
+    drop(guard);
+    Ok(())
+}